@@ -0,0 +1,95 @@
+//! Price-per-piece benchmarks by theme and year
+//!
+//! A Duplo brick and a Technic pin aren't worth the same, so a per-piece price
+//! only means something next to sets of the same theme and year. Segments
+//! with too few sets to say fall back to their whole theme.
+
+use crate::results::read_results;
+use crate::set_list::{read_set_list, SET_LIST_PATH};
+
+use polars::prelude::*;
+use std::path::Path;
+
+// fewer sets than this in a theme and year and the theme is used instead
+const MIN_SEGMENT_SETS: u32 = 3;
+
+// fill in themes the scan missed from the set list, when there is one
+fn with_set_list_themes(lf: LazyFrame) -> LazyFrame {
+    if !Path::new(SET_LIST_PATH).exists() {
+        return lf;
+    }
+    let set_list = read_set_list()
+        .lazy()
+        .select([col("set_number"), col("theme").alias("set_list_theme")]);
+
+    lf.left_join(set_list, col("set_number"), col("set_number"))
+        .with_column(col("theme").fill_null(col("set_list_theme")))
+        .drop_columns(["set_list_theme"])
+}
+
+/// per-piece prices, the segment's benchmarks and how each listing compares
+///
+/// listed_vs_benchmark is how far the listed price per piece is above (or,
+/// when negative, below) the typical value per piece of its segment
+pub fn add_benchmarks(lf: LazyFrame) -> LazyFrame {
+    let segment = [col("theme"), col("year")];
+    let theme = [col("theme")];
+    // windows can't nest so each median gets its own column
+    let benchmark = |name: &str| {
+        when(col("segment_sets").gt_eq(lit(MIN_SEGMENT_SETS)))
+            .then(col(name).median().over(segment.clone()))
+            .otherwise(col(name).median().over(theme.clone()))
+            .alias(&format!("benchmark_{name}"))
+    };
+
+    with_set_list_themes(lf)
+        .with_columns([
+            (col("retail_price") / col("pieces")).alias("retail_per_piece"),
+            (col("value") / col("pieces")).alias("value_per_piece"),
+            col("set_number")
+                .count()
+                .over(segment.clone())
+                .alias("segment_sets"),
+        ])
+        .with_columns([benchmark("retail_per_piece"), benchmark("value_per_piece")])
+        .with_column(
+            (col("listed_price") / col("pieces") / col("benchmark_value_per_piece") - lit(1.0))
+                .alias("listed_vs_benchmark"),
+        )
+        .drop_columns(["segment_sets"])
+}
+
+/// print the per-piece benchmarks of each theme and year in the scan results
+pub fn benchmarks(results_path: &str) -> color_eyre::Result<()> {
+    let df = with_set_list_themes(read_results(results_path).lazy())
+        .groupby([col("theme"), col("year")])
+        .agg([
+            col("set_number").count().alias("sets"),
+            (col("retail_price") / col("pieces"))
+                .median()
+                .alias("retail_per_piece"),
+            (col("value") / col("pieces"))
+                .median()
+                .alias("value_per_piece"),
+            (col("listed_price") / col("pieces"))
+                .median()
+                .alias("listed_per_piece"),
+        ])
+        .sort_by_exprs([col("theme"), col("year")], [false, false], true)
+        .collect()
+        .expect("The per-piece benchmarks of the results.");
+
+    println!("{}", &df);
+    if df
+        .column("sets")
+        .expect("The set counts of the benchmarks.")
+        .u32()
+        .expect("Set counts as u32.")
+        .into_no_null_iter()
+        .any(|sets| sets < MIN_SEGMENT_SETS)
+    {
+        println!("note: listings in theme and years with fewer than {MIN_SEGMENT_SETS} sets are compared to their whole theme.");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,494 @@
+//! Choosing the best basket of deals that fits a budget
+
+use clap::ValueEnum;
+use polars::prelude::*;
+
+/// what a basket of deals should make as much of as possible
+#[derive(Clone, Copy, Debug, Default, ValueEnum)]
+pub enum Objective {
+    /// expected_profit after resale fees
+    #[default]
+    Profit,
+    /// value minus landed_cost, ignoring resale fees
+    ValueGain,
+}
+
+impl Objective {
+    fn gain(self) -> Expr {
+        match self {
+            Objective::Profit => col("expected_profit"),
+            Objective::ValueGain => col("value") - col("landed_cost"),
+        }
+    }
+}
+
+pub struct Budget {
+    // the most the basket's landed_cost can add up to
+    pub amount: f64,
+    pub objective: Objective,
+    // the most pieces the basket can have in total
+    pub max_pieces: Option<u32>,
+    // the most the basket can spend on shipping in total
+    pub max_shipping: Option<f64>,
+}
+
+/// the deals a budget picked and, when it took shortcuts to pick them, why
+/// they may not be the best basket
+pub struct Basket {
+    pub deals: DataFrame,
+    pub shortcuts: Vec<String>,
+}
+
+// the most deals considered for a basket, best gain per cost first
+const MAX_CANDIDATES: usize = 100;
+// the search with piece or shipping caps can take exponential time so it
+// considers fewer
+const MAX_CAPPED_CANDIDATES: usize = 32;
+// the most amounts the knapsack table has, i.e. a budget of 1,000 to the cent
+const MAX_STEPS: usize = 100_000;
+
+// a deal worth considering for the basket
+struct Candidate {
+    row: u32,
+    cost: f64,
+    gain: f64,
+    pieces: u32,
+    shipping: f64,
+}
+
+impl Budget {
+    /// the rows of `df` that together make the most of the objective within budget
+    ///
+    /// `df` needs the landed_cost columns from CostModel and, when shipping
+    /// wasn't estimated per set, `flat_shipping` is what each set costs to ship
+    pub fn basket(&self, df: &DataFrame, flat_shipping: f64) -> Basket {
+        let shipping = if df.column("shipping_cost").is_ok() {
+            col("shipping_cost").fill_null(lit(flat_shipping))
+        } else {
+            lit(flat_shipping)
+        };
+        let terms = df
+            .clone()
+            .lazy()
+            .select([
+                col("landed_cost"),
+                self.objective.gain().alias("gain"),
+                col("pieces").fill_null(lit(0u32)),
+                shipping.alias("shipping"),
+            ])
+            .collect()
+            .expect("The cost and gain of each deal.");
+        let f64 = |name: &str| {
+            terms
+                .column(name)
+                .unwrap_or_else(|_| panic!("The {name} column."))
+                .f64()
+                .unwrap_or_else(|_| panic!("{name} as f64."))
+                .clone()
+        };
+        let pieces = terms
+            .column("pieces")
+            .expect("The pieces column.")
+            .u32()
+            .expect("pieces as u32.")
+            .clone();
+
+        let mut candidates: Vec<Candidate> = f64("landed_cost")
+            .into_iter()
+            .zip(&f64("gain"))
+            .zip(&pieces)
+            .zip(&f64("shipping"))
+            .enumerate()
+            .filter_map(|(row, (((cost, gain), pieces), shipping))| {
+                Some(Candidate {
+                    row: row as u32,
+                    cost: cost?,
+                    gain: gain?,
+                    pieces: pieces.unwrap_or_default(),
+                    shipping: shipping.unwrap_or_default(),
+                })
+            })
+            // a deal that loses money never improves a basket
+            .filter(|candidate| candidate.gain > 0.0 && candidate.cost <= self.amount)
+            .collect();
+        // best gain per unit of cost first, which is also what gets cut
+        candidates.sort_by(|a, b| {
+            (b.gain / b.cost)
+                .partial_cmp(&(a.gain / a.cost))
+                .expect("A comparable gain per cost.")
+        });
+
+        let uncapped = self.max_pieces.is_none() && self.max_shipping.is_none();
+        let max_candidates = if uncapped {
+            MAX_CANDIDATES
+        } else {
+            MAX_CAPPED_CANDIDATES
+        };
+        let mut shortcuts = vec![];
+        if candidates.len() > max_candidates {
+            shortcuts.push(format!(
+                "only the {max_candidates} deals with the most gain per cost of {} were considered",
+                candidates.len()
+            ));
+            candidates.truncate(max_candidates);
+        }
+
+        let chosen = if uncapped {
+            let step = self.step();
+            if step > 1 {
+                shortcuts.push(format!(
+                    "costs were rounded up to steps of {:.2}",
+                    step as f64 / 100.0
+                ));
+            }
+            self.knapsack(&candidates, step)
+        } else {
+            Search::new(self, &candidates).best()
+        };
+
+        let mut rows: Vec<u32> = chosen.iter().map(|&index| candidates[index].row).collect();
+        rows.sort_unstable();
+        Basket {
+            deals: df
+                .take(&IdxCa::from_vec("rows", rows))
+                .expect("The deals in the basket."),
+            shortcuts,
+        }
+    }
+
+    // how many cents each amount of the knapsack table covers
+    fn step(&self) -> usize {
+        let budget_cents = (self.amount * 100.0).round() as usize;
+        budget_cents.div_ceil(MAX_STEPS).max(1)
+    }
+
+    // a 0/1 knapsack over the budget in whole cents, or coarser steps when
+    // the budget has more than MAX_STEPS cents. costs are rounded up to a step
+    // so the basket never goes over
+    fn knapsack(&self, candidates: &[Candidate], step: usize) -> Vec<usize> {
+        let capacity = (self.amount * 100.0).round() as usize / step;
+        let weights: Vec<usize> = candidates
+            .iter()
+            .map(|candidate| ((candidate.cost * 100.0).round() as usize).div_ceil(step))
+            .collect();
+
+        // the most gain within each amount and whether each candidate was
+        // taken to get it
+        let mut best = vec![0.0; capacity + 1];
+        let mut taken = vec![false; candidates.len() * (capacity + 1)];
+        for (index, candidate) in candidates.iter().enumerate() {
+            for room in (weights[index]..=capacity).rev() {
+                let with = best[room - weights[index]] + candidate.gain;
+                if with > best[room] {
+                    best[room] = with;
+                    taken[index * (capacity + 1) + room] = true;
+                }
+            }
+        }
+
+        let mut chosen = vec![];
+        let mut room = capacity;
+        for index in (0..candidates.len()).rev() {
+            if taken[index * (capacity + 1) + room] {
+                chosen.push(index);
+                room -= weights[index];
+            }
+        }
+        chosen
+    }
+}
+
+// a branch and bound search over which candidates go in the basket when
+// pieces or shipping are capped too
+struct Search<'a> {
+    budget: &'a Budget,
+    candidates: &'a [Candidate],
+    // candidate indices by gain per piece and per shipping cost, for the bound
+    by_pieces: Vec<usize>,
+    by_shipping: Vec<usize>,
+    chosen: Vec<usize>,
+    best: Vec<usize>,
+    best_gain: f64,
+}
+
+impl<'a> Search<'a> {
+    fn new(budget: &'a Budget, candidates: &'a [Candidate]) -> Self {
+        let by = |weight: fn(&Candidate) -> f64| {
+            let mut order: Vec<usize> = (0..candidates.len()).collect();
+            order.sort_by(|&a, &b| {
+                (candidates[b].gain / weight(&candidates[b]))
+                    .partial_cmp(&(candidates[a].gain / weight(&candidates[a])))
+                    .expect("A comparable gain per piece or shipping cost.")
+            });
+            order
+        };
+
+        Search {
+            budget,
+            candidates,
+            by_pieces: by(|candidate| candidate.pieces as f64),
+            by_shipping: by(|candidate| candidate.shipping),
+            chosen: vec![],
+            best: vec![],
+            best_gain: 0.0,
+        }
+    }
+
+    fn best(mut self) -> Vec<usize> {
+        self.branch(0, 0.0, 0.0, 0, 0.0);
+        self.best
+    }
+
+    fn branch(&mut self, index: usize, cost: f64, gain: f64, pieces: u32, shipping: f64) {
+        if gain > self.best_gain {
+            self.best_gain = gain;
+            self.best = self.chosen.clone();
+        }
+        if index == self.candidates.len()
+            || gain + self.bound(index, cost, pieces, shipping) <= self.best_gain
+        {
+            return;
+        }
+
+        let candidate = &self.candidates[index];
+        let fits = cost + candidate.cost <= self.budget.amount
+            && self
+                .budget
+                .max_pieces
+                .is_none_or(|max| pieces + candidate.pieces <= max)
+            && self
+                .budget
+                .max_shipping
+                .is_none_or(|max| shipping + candidate.shipping <= max);
+        if fits {
+            self.chosen.push(index);
+            self.branch(
+                index + 1,
+                cost + candidate.cost,
+                gain + candidate.gain,
+                pieces + candidate.pieces,
+                shipping + candidate.shipping,
+            );
+            self.chosen.pop();
+        }
+        self.branch(index + 1, cost, gain, pieces, shipping);
+    }
+
+    // the most the remaining candidates could add if they could be split,
+    // which is never less than what they can actually add. each cap gives
+    // its own bound and the tightest one counts
+    fn bound(&self, index: usize, cost: f64, pieces: u32, shipping: f64) -> f64 {
+        let order: Vec<usize> = (0..self.candidates.len()).collect();
+        let mut bound = self.fractional(&order, index, self.budget.amount - cost, |candidate| {
+            candidate.cost
+        });
+        if let Some(max) = self.budget.max_pieces {
+            bound = bound.min(self.fractional(
+                &self.by_pieces,
+                index,
+                f64::from(max - pieces),
+                |candidate| candidate.pieces as f64,
+            ));
+        }
+        if let Some(max) = self.budget.max_shipping {
+            bound = bound.min(self.fractional(
+                &self.by_shipping,
+                index,
+                max - shipping,
+                |candidate| candidate.shipping,
+            ));
+        }
+        bound
+    }
+
+    // the fractional knapsack of the candidates from `index` on over one cap
+    fn fractional(
+        &self,
+        order: &[usize],
+        index: usize,
+        mut room: f64,
+        weight: fn(&Candidate) -> f64,
+    ) -> f64 {
+        let mut bound = 0.0;
+        for candidate in order
+            .iter()
+            .filter(|&&order| order >= index)
+            .map(|&order| &self.candidates[order])
+        {
+            let weight = weight(candidate);
+            if weight <= room {
+                room -= weight;
+                bound += candidate.gain;
+            } else {
+                bound += candidate.gain * room / weight;
+                break;
+            }
+        }
+        bound
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a small seeded generator so the instances are the same every run
+    struct Lcg(u64);
+
+    impl Lcg {
+        fn below(&mut self, bound: u64) -> u64 {
+            self.0 = self
+                .0
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (self.0 >> 33) % bound
+        }
+    }
+
+    fn deals(random: &mut Lcg, count: usize) -> DataFrame {
+        let mut landed_cost = vec![];
+        let mut expected_profit = vec![];
+        let mut pieces = vec![];
+        let mut shipping_cost = vec![];
+        for _ in 0..count {
+            landed_cost.push((1_000 + random.below(40_000)) as f64 / 100.0);
+            // some deals lose money
+            expected_profit.push((random.below(15_000) as f64 - 2_000.0) / 100.0);
+            pieces.push(random.below(3_000) as u32);
+            shipping_cost.push(random.below(3_000) as f64 / 100.0);
+        }
+        df!(
+            "landed_cost" => landed_cost,
+            "expected_profit" => expected_profit,
+            "pieces" => pieces,
+            "shipping_cost" => shipping_cost
+        )
+        .unwrap()
+    }
+
+    fn column(df: &DataFrame, name: &str) -> Vec<f64> {
+        df.column(name)
+            .unwrap()
+            .cast(&DataType::Float64)
+            .unwrap()
+            .f64()
+            .unwrap()
+            .into_no_null_iter()
+            .collect()
+    }
+
+    // the most profit of any subset within every limit
+    fn brute_force(df: &DataFrame, budget: &Budget) -> f64 {
+        let cost = column(df, "landed_cost");
+        let profit = column(df, "expected_profit");
+        let pieces = column(df, "pieces");
+        let shipping = column(df, "shipping_cost");
+
+        let mut best: f64 = 0.0;
+        for subset in 0..1u32 << df.height() {
+            let total = |values: &[f64]| -> f64 {
+                (0..df.height())
+                    .filter(|row| subset & (1 << row) != 0)
+                    .map(|row| values[row])
+                    .sum()
+            };
+            let fits = total(&cost) <= budget.amount + 1e-9
+                && budget
+                    .max_pieces
+                    .is_none_or(|max| total(&pieces) <= f64::from(max))
+                && budget
+                    .max_shipping
+                    .is_none_or(|max| total(&shipping) <= max + 1e-9);
+            if fits {
+                best = best.max(total(&profit));
+            }
+        }
+        best
+    }
+
+    fn check(budget: Budget, instances: usize) {
+        let mut random = Lcg(42);
+        for _ in 0..instances {
+            let df = deals(&mut random, 12);
+            let basket = budget.basket(&df, 0.0);
+            assert!(basket.shortcuts.is_empty());
+            let basket = basket.deals;
+
+            let total = |name: &str| column(&basket, name).iter().sum::<f64>();
+            assert!(total("landed_cost") <= budget.amount + 1e-9);
+            if let Some(max) = budget.max_pieces {
+                assert!(total("pieces") <= f64::from(max));
+            }
+            if let Some(max) = budget.max_shipping {
+                assert!(total("shipping_cost") <= max + 1e-9);
+            }
+            let best = brute_force(&df, &budget);
+            assert!(
+                (total("expected_profit") - best).abs() < 1e-6,
+                "the basket makes {} but {best} is possible",
+                total("expected_profit")
+            );
+        }
+    }
+
+    #[test]
+    fn knapsack_matches_brute_force() {
+        check(
+            Budget {
+                amount: 800.0,
+                objective: Objective::Profit,
+                max_pieces: None,
+                max_shipping: None,
+            },
+            30,
+        );
+    }
+
+    #[test]
+    fn capped_search_matches_brute_force() {
+        check(
+            Budget {
+                amount: 800.0,
+                objective: Objective::Profit,
+                max_pieces: Some(5_000),
+                max_shipping: Some(40.0),
+            },
+            30,
+        );
+    }
+
+    #[test]
+    fn nothing_fits() {
+        let df = deals(&mut Lcg(7), 5);
+        let budget = Budget {
+            amount: 5.0,
+            objective: Objective::Profit,
+            max_pieces: None,
+            max_shipping: None,
+        };
+        assert_eq!(budget.basket(&df, 0.0).deals.height(), 0);
+    }
+
+    #[test]
+    fn shortcuts_are_reported() {
+        let df = deals(&mut Lcg(3), 150);
+        let budget = Budget {
+            amount: 5_000.0,
+            objective: Objective::Profit,
+            max_pieces: None,
+            max_shipping: None,
+        };
+        let shortcuts = budget.basket(&df, 0.0).shortcuts;
+        assert_eq!(shortcuts.len(), 2);
+        assert!(shortcuts[0].starts_with("only the 100 deals"));
+        assert_eq!(shortcuts[1], "costs were rounded up to steps of 0.05");
+
+        let budget = Budget {
+            max_pieces: Some(10_000),
+            ..budget
+        };
+        let shortcuts = budget.basket(&df, 0.0).shortcuts;
+        assert_eq!(shortcuts.len(), 1);
+        assert!(shortcuts[0].starts_with("only the 32 deals"));
+    }
+}
@@ -0,0 +1,42 @@
+//! Reporting sets whose prices moved between scans
+
+use crate::history::{History, Since, HISTORY_DB_PATH};
+
+use polars::prelude::*;
+
+/// sets whose listed price dropped or value rose by more than `threshold` percent
+pub fn changes(since: Since, threshold: f64) -> color_eyre::Result<()> {
+    let change = |column: &str| col(column) - col(&format!("previous_{column}"));
+    let percent_change =
+        |column: &str| change(column) / col(&format!("previous_{column}")) * lit(100.0);
+
+    let df = History::open(HISTORY_DB_PATH)
+        .changes(since)
+        .lazy()
+        .with_columns([
+            change("listed_price").alias("listed_price_change"),
+            percent_change("listed_price").alias("listed_price_change_percent"),
+            change("value").alias("value_change"),
+            percent_change("value").alias("value_change_percent"),
+        ])
+        .filter(
+            col("listed_price_change_percent")
+                .lt(lit(-threshold))
+                .fill_null(false)
+                .or(col("value_change_percent")
+                    .gt(lit(threshold))
+                    .fill_null(false)),
+        )
+        // biggest drops first
+        .sort("listed_price_change_percent", Default::default())
+        .collect()
+        .expect("The changes between scans.");
+
+    if df.height() == 0 {
+        println!("No listed price drops or value rises over {threshold}% since then.");
+    } else {
+        println!("{}", &df);
+    }
+
+    Ok(())
+}
@@ -1,43 +1,46 @@
 //! Command line parsing and logic
 
-use crate::scraper_utils::{make_selector, throttle};
+use crate::benchmarks::{add_benchmarks, benchmarks};
+use crate::budget::{Budget, Objective};
+use crate::changes::changes;
+use crate::costs::CostModel;
+use crate::daemon::{
+    dated_output, parse_cron, parse_interval, run_on_complete, shutdown_signal, Schedule,
+};
+use crate::exchange::ExchangeRates;
+use crate::expr::{self, Ast};
+use crate::history::{History, Since, HISTORY_DB_PATH};
+use crate::investment::investment_metrics;
+use crate::lookup::lookup;
+use crate::lot::{lot_report, parse_ask, read_lot};
+use crate::money::Currency;
+use crate::notify::Notifier;
+use crate::outliers::flag_outliers;
+use crate::portfolio::{portfolio_report, read_portfolio, PORTFOLIO_PATH};
+use crate::price_history::{PriceHistory, PRICE_HISTORY_PATH};
+use crate::region::Region;
+use crate::scoring::ScoringStrategy;
+use crate::scrape::scrape_set;
 use crate::set_data::SetData;
-
-use clap::Parser;
-use lazy_static::lazy_static;
+use crate::set_list::{read_set_list, with_variant, write_set_list, SET_LIST_COLUMNS};
+use crate::shipping::ShippingRates;
+use crate::sql::{sql, SqlFormat};
+use crate::stats::{stats, StatsBy};
+use crate::watchlist::{read_watchlist, WATCHLIST_HIT_EXIT_CODE, WATCHLIST_PATH};
+
+use chrono::{Datelike, Local};
+use clap::{ArgGroup, Parser, Subcommand};
+use color_eyre::eyre::eyre;
 use polars::prelude::*;
-use regex::Regex;
-use scraper::{Html, Selector};
-use std::fs::File;
-
-lazy_static! {
-    // create selectors
-    static ref TABLE: Selector = make_selector("table");
-    static ref TR: Selector = make_selector("tr");
-    static ref TD: Selector = make_selector("td");
-    static ref H4: Selector = make_selector("h4");
-    static ref H4_A: Selector = make_selector("h4 a");
-    static ref SET_DETAILS: Selector = make_selector("div#SetDetails div.row");
-    static ref COL_XS_5: Selector = make_selector("div.col-xs-5");
-    static ref COL_XS_7: Selector = make_selector("div.col-xs-7");
-    static ref TABLE_TR_TD_H1: Selector = make_selector("table tr td h1");
-    // gets listed price
-    static ref TABLE_TR_TD_DIV_SPAN_A: Selector = make_selector("table#sales_region_table tr td div span.a");
-    // these literally says 'placeholder' so this might break
-    static ref PRICE_ROWS_SELECTOR: Selector = make_selector("#ContentPlaceHolder1_PanelSetPricing div.row");
-    // value is nested under a hover
-    static ref SPAN_HELPPOPOVER: Selector = make_selector("span.helppopover");
-
-    // create regular expressions
-    // if there is no ',' then the regex fails to find a second "set" of digits
-    static ref RE_NUMBER_THEN_AMPERSAND: Regex = Regex::new(r"(\d+,?\d?+)&?").expect("A Regex of a number before an '&'.");
-    static ref RE_DOLLARS: Regex = Regex::new(r"\$(\d?+,?\d?+\.\d?+)").expect("A Regex of a dollar amount after the '$'.");
-    static ref RE_YEAR: Regex = Regex::new(r"[\s>](\d{4})[<\s]").expect("A Regex for a 4 digit number.");
-}
+use std::fs::{File, OpenOptions};
+use std::time::Duration;
 
-#[derive(Parser)]
+#[derive(Clone, Parser)]
 #[command(author, version, about, long_about = None)]
 pub struct Leget {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     // try to limit inputs to just valid years
     /// the year made of sets you want to scan for. e.g. 2020 2021 2022 etc.
     #[arg(value_parser = clap::value_parser!(u16).range(1949..2200))]
@@ -52,19 +55,196 @@ pub struct Leget {
     #[arg(short = 'r', long, group = "sets", num_args = 2)]
     set_range: Option<Vec<u32>>,
 
-    // default to 1200 due to shipping costs being main usage
     /// the smallest number of pieces a set should have
     #[arg(long, default_value_t = 1)]
     min_pieces: u32,
 
     /// the largest number of pieces a set should have
-    #[arg(long, default_value_t = 1200)]
-    max_pieces: u32,
+    #[arg(long)]
+    max_pieces: Option<u32>,
 
     // TODO: this might want to be a subcommand
     /// use this to update the file that lists valid sets
     #[arg(short = 'S', long, group = "sets", num_args = 2)]
     update_set_list: Option<Vec<u32>>,
+
+    /// the sales region to take listings and retail prices from
+    #[arg(long, value_enum, default_value_t = Region::Us)]
+    region: Region,
+
+    /// convert all prices to this currency using the --rates file
+    #[arg(long, value_enum)]
+    currency: Option<Currency>,
+
+    /// a csv of exchange rates with columns currency,per_usd,as_of
+    #[arg(long, default_value = "rates.csv")]
+    rates: String,
+
+    /// a csv of shipping weight tiers with columns max_weight_kg,cost. sets
+    /// heavier than its last tier are left out
+    #[arg(long)]
+    shipping_rates: Option<String>,
+
+    /// cm³ per kg used to turn box size into dimensional weight
+    #[arg(long, default_value_t = 5000.0)]
+    dim_divisor: f64,
+
+    /// the most you're willing to pay to ship a set. needs --shipping-rates
+    #[arg(long, requires = "shipping_rates")]
+    max_shipping_cost: Option<f64>,
+
+    /// flat shipping per set when there's no estimate from --shipping-rates
+    #[arg(long, default_value_t = 0.0)]
+    shipping: f64,
+
+    /// sales tax paid when buying, as a percent
+    #[arg(long, default_value_t = 0.0)]
+    tax_rate: f64,
+
+    /// marketplace fees taken when reselling, as a percent
+    #[arg(long, default_value_t = 0.0)]
+    fee_rate: f64,
+
+    /// also save each set's charted value history to price_history.csv
+    #[arg(long)]
+    price_history: bool,
+
+    /// opt out of recording this scan in the leget.db history
+    #[arg(long)]
+    skip_history: bool,
+
+    /// post deals and watchlist hits as json to this url
+    #[arg(long)]
+    webhook_url: Option<String>,
+
+    /// how far below value, as a percent, a listing must be to post it
+    #[arg(long, default_value_t = 20.0)]
+    notify_threshold: f64,
+
+    /// how many times to retry a failed webhook post
+    #[arg(long, default_value_t = 3)]
+    webhook_retries: u32,
+
+    /// how to rank deals; each adds a score column where higher is better
+    #[arg(long, value_enum, default_value_t = ScoringStrategy::PerPiece)]
+    score: ScoringStrategy,
+
+    /// rank by this expression over the output columns instead, e.g. "(value - listed_price) / pieces"
+    #[arg(long, value_parser = expr::parse, conflicts_with = "score", allow_hyphen_values = true)]
+    score_expr: Option<Ast>,
+
+    /// only keep sets matching this filter, e.g. 'value > 100 and theme = "Technic"'
+    #[arg(long = "where", value_parser = expr::parse, allow_hyphen_values = true)]
+    where_filter: Option<Ast>,
+
+    /// how many robust z-scores from its peers a listing's price to value must be to flag it
+    #[arg(long, default_value_t = 3.5)]
+    outlier_threshold: f64,
+
+    /// leave listings flagged as outliers out of the results
+    #[arg(long)]
+    exclude_outliers: bool,
+
+    /// pick a basket of deals that makes the most it can within this total
+    /// landed cost; with many deals or a large budget it may not be the best one
+    #[arg(long)]
+    budget: Option<f64>,
+
+    /// what the --budget basket should make the most of
+    #[arg(long, value_enum, default_value_t = Objective::Profit, requires = "budget")]
+    objective: Objective,
+
+    /// the most pieces the --budget basket can have in total
+    #[arg(long, requires = "budget")]
+    budget_max_pieces: Option<u32>,
+
+    /// the most the --budget basket can spend on shipping in total
+    #[arg(long, requires = "budget")]
+    budget_max_shipping: Option<f64>,
+
+    /// where to write the scan results
+    #[arg(long, default_value = "legot.csv")]
+    output: String,
+
+    // the daemon appends each run to the day's output file
+    #[arg(skip)]
+    append_output: bool,
+}
+
+#[derive(Clone, Subcommand)]
+enum Command {
+    /// find a set in the set list from a scanned barcode
+    Lookup {
+        /// a UPC or EAN barcode
+        #[arg(long)]
+        upc: String,
+    },
+    /// print the recorded value and listed price of a set over time
+    History {
+        /// e.g. 10300 or 10300-1
+        set_number: String,
+    },
+    /// list sets whose listed price dropped or value rose between scans
+    Changes {
+        /// 'last' for each set's previous scan or a YYYY-MM-DD date
+        #[arg(long, default_value = "last")]
+        since: Since,
+
+        /// the smallest percent change worth reporting
+        #[arg(long, default_value_t = 0.0)]
+        threshold: f64,
+    },
+    /// scan the watchlist and report sets listed at or below their target price
+    Check {
+        /// a csv with columns set_number,target_price
+        #[arg(long, default_value = WATCHLIST_PATH)]
+        watchlist: String,
+    },
+    /// value the sets we own and report their gains overall and by theme
+    Portfolio {
+        /// a csv with columns set_number,condition,purchase_price,purchase_date,quantity
+        #[arg(default_value = PORTFOLIO_PATH)]
+        portfolio: String,
+    },
+    /// value a lot of sets against its asking price
+    Lot {
+        /// a csv with columns set_number,condition,quantity
+        lot: String,
+
+        /// the asking price for the whole lot in --currency, or USD without it
+        #[arg(long, value_parser = parse_ask)]
+        ask: f64,
+    },
+    /// run a SQL query over the sets, previous_sets, set_list, scans and records tables
+    Sql {
+        /// e.g. "SELECT theme, avg(value / retail_price) FROM sets GROUP BY theme"
+        query: String,
+
+        #[arg(long, value_enum, default_value_t = SqlFormat::Table)]
+        format: SqlFormat,
+    },
+    /// summarize the scan results by theme, subtheme or year
+    Stats {
+        #[arg(long, value_enum, default_value_t = StatsBy::Theme)]
+        by: StatsBy,
+    },
+    /// print the retail and value per piece of each theme and year in the scan results
+    Benchmarks,
+    /// rerun the scan given by the other options on a schedule until stopped
+    #[command(group(ArgGroup::new("schedule").required(true).args(["every", "cron"])))]
+    Daemon {
+        /// time between scans, e.g. 90s, 30m, 6h or 1d
+        #[arg(long, value_parser = parse_interval)]
+        every: Option<Duration>,
+
+        /// a cron expression with seconds, e.g. "0 0 6 * * *" for 6am daily
+        #[arg(long, value_parser = parse_cron)]
+        cron: Option<Box<cron::Schedule>>,
+
+        /// a shell command to run after each scan; LEGET_OUTPUT holds the output file
+        #[arg(long)]
+        on_complete: Option<String>,
+    },
 }
 
 impl Leget {
@@ -74,32 +254,286 @@ impl Leget {
     }
 
     pub async fn exec(mut self) -> color_eyre::Result<()> {
+        match self.command.take() {
+            Some(Command::Lookup { upc }) => lookup(&upc),
+            Some(Command::History { set_number }) => {
+                let history =
+                    History::open(HISTORY_DB_PATH).set_history(&with_variant(&set_number));
+                println!("{}", &history);
+                Ok(())
+            }
+            Some(Command::Changes { since, threshold }) => changes(since, threshold),
+            Some(Command::Check { watchlist }) => self.check(&watchlist).await,
+            Some(Command::Portfolio { portfolio }) => {
+                let portfolio = read_portfolio(&portfolio);
+                let set_numbers = portfolio
+                    .column("set_number")
+                    .expect("The set numbers of the portfolio.")
+                    .unique()
+                    .expect("The unique set numbers of the portfolio.");
+                let sets = self
+                    .refresh(set_numbers.utf8().expect("Set numbers as utf8."))
+                    .await;
+                portfolio_report(portfolio, sets, self.value_currency())
+            }
+            Some(Command::Lot { lot, ask }) => {
+                let lot = read_lot(&lot);
+                let set_numbers = lot
+                    .column("set_number")
+                    .expect("The set numbers of the lot.")
+                    .unique()
+                    .expect("The unique set numbers of the lot.");
+                let sets = self
+                    .refresh(set_numbers.utf8().expect("Set numbers as utf8."))
+                    .await;
+                lot_report(lot, sets, ask, self.value_currency())
+            }
+            Some(Command::Sql { query, format }) => sql(&query, &self.output, format),
+            Some(Command::Stats { by }) => stats(&self.output, by),
+            Some(Command::Benchmarks) => benchmarks(&self.output),
+            Some(Command::Daemon {
+                every,
+                cron,
+                on_complete,
+            }) => {
+                let schedule = match (every, cron) {
+                    (Some(interval), _) => Schedule::Every(interval),
+                    (None, Some(cron)) => Schedule::Cron(cron),
+                    (None, None) => unreachable!("clap requires --every or --cron"),
+                };
+                self.daemon(schedule, on_complete).await
+            }
+            None => self.scan().await,
+        }
+    }
+
+    // values are always scraped in USD so they're only in the listings'
+    // currency once converted
+    fn value_currency(&self) -> Currency {
+        self.currency.unwrap_or(Currency::Usd)
+    }
+
+    fn warn_mixed_currencies(&self) {
+        if self.currency.is_none() && self.region.currency() != Currency::Usd {
+            println!("warning: values are in USD but listings are in {}. Use --currency to compare them in one currency.", self.region.currency().code());
+        }
+    }
+
+    /// scrape just these sets and convert them to the output currency
+    async fn refresh(&self, set_numbers: &Utf8Chunked) -> LazyFrame {
+        let client = reqwest::Client::new();
+
+        self.warn_mixed_currencies();
+        let mut set_data = SetData::new();
+        for set_number in set_numbers.into_no_null_iter() {
+            // a mistyped or removed set shouldn't stop the rest being checked
+            if let Err(error) =
+                scrape_set(&client, set_number, self.region, &mut set_data, None).await
+            {
+                println!("warning: skipping {error}");
+            }
+        }
+
+        let mut lf = set_data.into_dataframe().lazy();
+        if let Some(currency) = self.currency {
+            let rates = ExchangeRates::from_path(&self.rates);
+            lf = rates.convert(lf, self.region.currency(), currency);
+        }
+        lf
+    }
+
+    async fn check(&self, watchlist: &str) -> color_eyre::Result<()> {
+        let watchlist = read_watchlist(watchlist);
+        let set_numbers = watchlist
+            .column("set_number")
+            .expect("The set numbers of the watchlist.")
+            .utf8()
+            .expect("Set numbers as utf8.");
+
+        let hits = self
+            .refresh(set_numbers)
+            .await
+            .inner_join(watchlist.lazy(), col("set_number"), col("set_number"))
+            .filter(col("listed_price").lt_eq(col("target_price")))
+            .select([
+                col("set_number"),
+                col("name"),
+                col("listed_price"),
+                col("target_price"),
+                col("value"),
+                col("currency"),
+            ])
+            .collect()
+            .expect("The watched sets at or below their target price.");
+
+        if hits.height() == 0 {
+            println!("No watched sets are listed at or below their target price.");
+            return Ok(());
+        }
+        println!("{}", &hits);
+        if let Some(ref url) = self.webhook_url {
+            Notifier::new(url, self.webhook_retries).notify(&hits).await;
+        }
+        // let cron and scripts tell a hit apart from a clean run
+        std::process::exit(WATCHLIST_HIT_EXIT_CODE);
+    }
+
+    async fn daemon(
+        self,
+        schedule: Schedule,
+        on_complete: Option<String>,
+    ) -> color_eyre::Result<()> {
+        // listen from the start so a SIGTERM mid-scan still stops us cleanly
+        let mut shutdown = tokio::spawn(shutdown_signal());
+
+        // an interval starts right away but a cron waits for its first time
+        let mut wait = matches!(schedule, Schedule::Cron(_));
+        loop {
+            if wait {
+                tokio::select! {
+                    _ = tokio::time::sleep(schedule.until_next()) => {}
+                    _ = &mut shutdown => break,
+                }
+            }
+            wait = true;
+
+            let mut run = self.clone();
+            run.output = dated_output(&self.output, Local::now().date_naive());
+            run.append_output = true;
+            let output = run.output.clone();
+
+            // let a scan that's underway finish so its history, output and
+            // notifications all happen or none do
+            let mut scan = tokio::spawn(run.scan());
+            let mut stop = false;
+            let scan = tokio::select! {
+                scan = &mut scan => scan,
+                _ = &mut shutdown => {
+                    println!("Finishing the current scan before stopping.");
+                    stop = true;
+                    scan.await
+                }
+            };
+            // a failed scan shouldn't take the daemon down with it
+            match scan {
+                Ok(Ok(())) => {
+                    if let Some(command) = &on_complete {
+                        run_on_complete(command, &output);
+                    }
+                }
+                Ok(Err(error)) => println!("warning: the scan failed: {error}"),
+                Err(error) => println!("warning: the scan failed: {error}"),
+            }
+            if stop {
+                break;
+            }
+        }
+
+        println!("Stopping the daemon.");
+        Ok(())
+    }
+
+    /// convert, cost, filter and score the scanned sets, best deals first
+    fn rank(&self, mut lf: LazyFrame) -> Result<LazyFrame, String> {
+        if let Some(currency) = self.currency {
+            let rates = ExchangeRates::from_path(&self.rates);
+            lf = rates.convert(lf, self.region.currency(), currency);
+        }
+        if let Some(ref path) = self.shipping_rates {
+            let shipping_rates = ShippingRates::from_path(path, self.dim_divisor);
+            lf = lf
+                .filter(shipping_rates.fits())
+                .with_column(shipping_rates.shipping_cost())
+                .with_column((col("listed_price") + col("shipping_cost")).alias("landed_price"));
+            if let Some(max_shipping_cost) = self.max_shipping_cost {
+                // less than or equal covers nulls
+                lf = lf.filter(col("shipping_cost").lt_eq(max_shipping_cost));
+            }
+        }
+        if let Some(max_pieces) = self.max_pieces {
+            lf = lf.filter(col("pieces").lt(max_pieces));
+        }
+        let cost_model = CostModel {
+            tax_rate: self.tax_rate,
+            fee_rate: self.fee_rate,
+            flat_shipping: self.shipping,
+        };
+        lf = lf.with_columns(cost_model.columns(self.shipping_rates.is_some()));
+        lf = investment_metrics(lf, Local::now().year());
+        lf = lf
+            .filter(col("listed_price").is_not_null())
+            .filter(col("value").is_not_null())
+            // greater than covers nulls
+            .filter(col("pieces").gt(self.min_pieces))
+            .with_column(
+                ((col("listed_price") - col("value")) / col("value"))
+                    .alias("percent_discount_from_value"),
+            )
+            .with_column(
+                (col("percent_discount_from_value") / col("pieces"))
+                    .alias("percent_discount_from_value_per_piece"),
+            );
+        lf = flag_outliers(lf, self.outlier_threshold);
+        if self.exclude_outliers {
+            lf = lf.filter(col("outlier").not());
+        }
+        lf = add_benchmarks(lf);
+
+        let score = match self.score_expr {
+            // the expression can use any column so far, including the costs
+            Some(ref ast) => {
+                let schema = lf.schema().expect("The schema of the scanned sets.");
+                ast.to_score(&schema)
+                    .map_err(|error| format!("--score-expr: {error}"))?
+                    .alias("score")
+            }
+            None => self.score.score(),
+        };
+        lf = lf.with_column(score);
+        if let Some(ref filter) = self.where_filter {
+            // the filter can use the score too
+            let schema = lf.schema().expect("The schema of the scored sets.");
+            let filter = filter
+                .to_filter(&schema)
+                .map_err(|error| format!("--where: {error}"))?;
+            lf = lf.filter(filter);
+        }
+        lf = lf.sort(
+            "score",
+            SortOptions {
+                descending: true,
+                nulls_last: true,
+            },
+        );
+
+        Ok(lf)
+    }
+
+    async fn scan(mut self) -> color_eyre::Result<()> {
         if let Some(ref range) = self.set_range {
             assert!(
                 range[0] < range[1],
                 "Range should be giving small -> large."
             );
         }
+        // catch a bad --score-expr or --where before spending time scraping
+        if (self.score_expr.is_some() || self.where_filter.is_some())
+            && self.update_set_list.is_none()
+        {
+            // collected so polars type checks it too, e.g. 'name + 1'
+            self.rank(SetData::new().into_dataframe().lazy())
+                .map_err(|error| eyre!(error))?
+                .collect()
+                .map_err(|error| eyre!("--score-expr or --where: {error}"))?;
+        }
         let mut set_data = SetData::new();
+        let mut price_history = PriceHistory::new();
 
         // We can scrape the site with a stays-alive connection
         let client = reqwest::Client::new();
 
-        // Read in stored list of sets
-        // append doesn't work if dtypes are mismatched; defaults are mismatched on read of csv
-        let mut set_list_schema = Schema::new();
-        set_list_schema.with_column("set_number".to_string(), DataType::Utf8);
-        set_list_schema.with_column("year".to_string(), DataType::Utf8);
-        set_list_schema.with_column("pieces".to_string(), DataType::Float32);
-
         // read in the set list
-        let set_list_lf: LazyFrame = CsvReader::from_path("set_list.csv")
-            .expect("A reader connection to set_list.csv")
-            .with_dtypes(Some(&set_list_schema))
-            .has_header(true)
-            .finish()
-            .expect("A polars DataFrame from set_list.csv")
-            .lazy();
+        let set_list_lf: LazyFrame = read_set_list().lazy();
 
         // gather set range into a vec so we can make a df
         let mut set_list_vec: Vec<String> = vec![];
@@ -141,18 +575,22 @@ impl Leget {
                 .expect("A DataFrame of my sets to filter to.")
                 .lazy();
 
-                let joined_lf = set_list_lf
-                    // filters here should effect final list via inner join
-                    .filter(col("pieces").lt(self.max_pieces))
-                    .filter(col("pieces").gt(self.min_pieces))
-                    .inner_join(sets_lf, col("set_number"), col("set_number"));
+                // filters here should effect final list via inner join
+                let mut joined_lf = set_list_lf.filter(col("pieces").gt(self.min_pieces));
+                if let Some(max_pieces) = self.max_pieces {
+                    joined_lf = joined_lf.filter(col("pieces").lt(max_pieces));
+                }
+                // only the parts of --where the set list has columns for
+                if let Some(prefilter) = self.where_filter.as_ref().and_then(|filter| {
+                    filter.prefilter(&joined_lf.schema().expect("The set list's schema."))
+                }) {
+                    joined_lf = joined_lf.filter(prefilter);
+                }
+                let joined_lf = joined_lf.inner_join(sets_lf, col("set_number"), col("set_number"));
 
                 // check for any years provided
                 let df = if let Some(ref year_vec) = self.years {
-                    let year_vec = year_vec
-                        .iter()
-                        .map(|n| n.to_string())
-                        .collect::<Vec<String>>();
+                    let year_vec = year_vec.iter().map(|n| *n as i32).collect::<Vec<i32>>();
                     let s_years: Series = Series::new("year", &year_vec);
                     let year_lf: LazyFrame = DataFrame::new(vec![s_years])
                         .expect("A polars df of years.")
@@ -198,7 +636,7 @@ impl Leget {
         }
 
         // Scrape by set numbers
-        if let Some(range) = self.set_range {
+        if let Some(range) = self.set_range.take() {
             for set_number in range[0]..=range[1] {
                 // check values against set_list
                 let mut set_number: String = set_number.to_string();
@@ -212,320 +650,29 @@ impl Leget {
                     continue;
                 }
 
-                let url = format!("https://www.brickeconomy.com/set/{}/", set_number);
-
-                // TODO: is there a way to get this to play nice with async? Maybe with a tower
-                // service?
-                throttle();
-                let response = client.get(url).send().await.expect("An async get request.");
-
-                match response.status() {
-                    reqwest::StatusCode::OK => {
-                        let content = response
-                            .text()
-                            .await
-                            .expect("The text of the get response.");
-                        let document = Html::parse_document(&content);
-
-                        let set_details = document.select(&SET_DETAILS);
-
-                        // Catch all other edge cases so that the columns are the same len
-                        // sometimes the header isn't even there, not sure if forcing it is the best
-                        if set_data.set_number.len() > set_data.pieces.len() {
-                            set_data.pieces.push(None);
-                        }
-                        assert_eq!(
-                            &set_data.set_number.len(),
-                            &set_data.pieces.len(),
-                            "Set number and pieces columns aren't the same length after set #{:?}.",
-                            set_data
-                                .set_number
-                                .last()
-                                .expect("The last value of set_data.set_number.")
-                        );
-                        // sometimes the value isn't there
-                        if set_data.set_number.len() > set_data.value.len() {
-                            set_data.value.push(None);
-                            println!(
-                                "last set: {:?}",
-                                set_data
-                                    .set_number
-                                    .last()
-                                    .expect("The last value in set_data.set_number.")
-                            )
-                        }
-                        assert_eq!(
-                            &set_data.set_number.len(),
-                            &set_data.value.len(),
-                            "Set number and pieces columns aren't the same length after set #{:?}.",
-                            set_data
-                                .set_number
-                                .last()
-                                .expect("The last value of set_data.set_number.")
-                        );
-                        // sometimes there just isn't a place to get the retail price
-                        if set_data.set_number.len() > set_data.retail_price.len() {
-                            set_data.retail_price.push(None);
-                        }
-                        assert_eq!(
-                            &set_data.set_number.len(),
-                            &set_data.retail_price.len(),
-                            "Set number and retail_price columns aren't the same length after set #{:?}.",
-                            set_data
-                                .set_number
-                                .last()
-                                .expect("The last value of set_data.set_number.")
-                        );
-
-                        // push one item at a time incase there are multiple
-                        // push set number (as a string because of the '-')
-                        if set_data.set_number.len() == set_data.name.len() {
-                            for detail in set_details {
-                                let mut header = detail.select(&COL_XS_5);
-                                let mut item = detail.select(&COL_XS_7);
-
-                                // sometimes a header is repeated; e.g. new and used Value
-                                let mut value_header_count = 0;
-
-                                if let Some(header) = header.next() {
-                                    let header = header.inner_html();
-                                    match header.as_str() {
-                                        "Set number" => set_data.set_number.push(
-                                            item.next()
-                                                .expect("The next set number from set details.")
-                                                .inner_html(),
-                                        ),
-                                        "Name" => {
-                                            set_data.name.push(
-                                                item.next()
-                                                    .expect("The next name from set details.")
-                                                    .inner_html(),
-                                            );
-                                        }
-                                        "Year" => {
-                                            if let Some(year) = item.next() {
-                                                let year = year.inner_html();
-                                                let numbers = RE_YEAR.captures(&year);
-                                                let numbers = numbers.expect(
-                                                    "The matches of a regex for 4 digit numbers.",
-                                                );
-                                                let year = &numbers[1];
-                                                set_data.year.push(Some(year.to_string()));
-                                            } else {
-                                                set_data.year.push(None);
-                                            }
-                                        }
-                                        "Pieces" => {
-                                            if let Some(pieces) = item.next() {
-                                                let piece_count = pieces.inner_html();
-                                                let numbers =
-                                                    RE_NUMBER_THEN_AMPERSAND.captures(&piece_count);
-                                                let numbers = numbers.expect("The matches of a regex with a number before an '&'.");
-                                                let piece_count =
-                                                    numbers[1].split(',').collect::<String>();
-                                                if let Ok(count) = piece_count.parse::<f32>() {
-                                                    set_data.pieces.push(Some(count));
-                                                } else {
-                                                    set_data.pieces.push(None);
-                                                }
-                                            } else {
-                                                set_data.pieces.push(None);
-                                            }
-                                        }
-                                        _ => continue,
-                                    }
-
-                                    // push other items only once per valid set number
-                                    if header.as_str() == "Set number" {
-                                        // push listed price
-                                        let mut listed_price =
-                                            document.select(&TABLE_TR_TD_DIV_SPAN_A);
-                                        if let Some(price) = listed_price.next() {
-                                            let price = price.inner_html();
-                                            let price = RE_DOLLARS.captures(&price).expect(
-                                                "The matches of a regex with a number after a '$'.",
-                                            );
-                                            if let Ok(price) = price[1].parse::<f32>() {
-                                                set_data.listed_price.push(Some(price));
-                                            } else {
-                                                set_data.listed_price.push(None);
-                                            }
-                                        } else {
-                                            set_data.listed_price.push(None);
-                                        }
-
-                                        // push prices
-                                        let price_rows = document.select(&PRICE_ROWS_SELECTOR);
-                                        for row in price_rows {
-                                            let headers = row.select(&COL_XS_5);
-                                            let mut items = row.select(&COL_XS_7);
-
-                                            for header in headers {
-                                                let mut header_html = header.inner_html();
-                                                let item = items.next();
-
-                                                // some headers are further nested
-                                                let value_headers =
-                                                    header.select(&SPAN_HELPPOPOVER);
-                                                for header in value_headers {
-                                                    header_html = header.inner_html();
-                                                }
-
-                                                match header_html.as_str() {
-                                                    "Retail price" => {
-                                                        if let Some(price) = item {
-                                                            let price = price.inner_html();
-                                                            let price = RE_DOLLARS.captures(&price);
-                                                            if let Some(price) = price {
-                                                                if let Ok(price) =
-                                                                    price[1].parse::<f32>()
-                                                                {
-                                                                    set_data
-                                                                        .retail_price
-                                                                        .push(Some(price));
-                                                                }
-                                                            } else {
-                                                                set_data.retail_price.push(None);
-                                                            }
-                                                        } else {
-                                                            set_data.retail_price.push(None);
-                                                        }
-                                                    }
-                                                    // as either market price or brickeconomy estimate
-                                                    // depending if the set is still availible at retail
-                                                    "Value" | "Market price" => {
-                                                        // sometimes there are both new and used
-                                                        // values; new seems to be first
-                                                        value_header_count += 1;
-                                                        if value_header_count == 1 {
-                                                            if let Some(price) = item {
-                                                                // not using inner html since sometimes
-                                                                // there is an additional <b> nested
-                                                                let price = price.html();
-                                                                let price =
-                                                                    RE_DOLLARS.captures(&price);
-                                                                if let Some(price) = price {
-                                                                    // regex doesn't handle ',' from
-                                                                    // numbers that get into the
-                                                                    // thousands
-                                                                    let price = price[1]
-                                                                        .split(',')
-                                                                        .collect::<String>();
-                                                                    if let Ok(price) =
-                                                                        price.parse::<f32>()
-                                                                    {
-                                                                        set_data
-                                                                            .value
-                                                                            .push(Some(price));
-                                                                    }
-                                                                } else {
-                                                                    set_data.value.push(None);
-                                                                }
-                                                            }
-                                                        }
-                                                    }
-                                                    _ => {}
-                                                }
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                    problem => {
-                        panic!("There was a problem: {:?}", problem);
-                    }
-                }
-                // sometimes the value isn't there
-                if set_data.set_number.len() > set_data.value.len() {
-                    set_data.value.push(None);
+                if let Err(error) = scrape_set(
+                    &client,
+                    &set_number,
+                    self.region,
+                    &mut set_data,
+                    self.price_history.then_some(&mut price_history),
+                )
+                .await
+                {
+                    println!("warning: skipping {error}");
                 }
-                // need to catch if the html is being missed somewhere
-                // TODO: could probably clean up some alerts
-                assert_eq!(
-                    &set_data.set_number.len(),
-                    &set_data.year.len(),
-                    "Set number and year columns aren't the same length after set #{:?}.",
-                    set_data
-                        .set_number
-                        .last()
-                        .expect("The last value in set_data.set_number.")
-                );
-            }
-            // sometimes there just isn't a place to get the data and
-            // we're on the last get request so the manual push misses
-            if set_data.set_number.len() > set_data.retail_price.len() {
-                set_data.retail_price.push(None);
-            }
-            if set_data.set_number.len() > set_data.value.len() {
-                set_data.value.push(None);
-            }
-            if set_data.set_number.len() > set_data.pieces.len() {
-                set_data.pieces.push(None);
             }
         }
 
-        // make sure the index len is the same before we make a dataframe
-        assert_eq!(
-            &set_data.set_number.len(),
-            &set_data.name.len(),
-            "Set number and name columns aren't the same length."
-        );
-        assert_eq!(
-            &set_data.set_number.len(),
-            &set_data.year.len(),
-            "Set number and year columns aren't the same length."
-        );
-        assert_eq!(
-            &set_data.set_number.len(),
-            &set_data.retail_price.len(),
-            "Set number and retail price columns aren't the same length."
-        );
-        assert_eq!(
-            &set_data.set_number.len(),
-            &set_data.value.len(),
-            "Set number and value columns aren't the same length."
-        );
-        assert_eq!(
-            &set_data.set_number.len(),
-            &set_data.listed_price.len(),
-            "Set number and listed price columns aren't the same length."
-        );
-        assert_eq!(
-            &set_data.set_number.len(),
-            &set_data.pieces.len(),
-            "Set number and pieces columns aren't the same length after set #{:?}.",
-            set_data
-                .set_number
-                .last()
-                .expect("The last value of set_data.set_number.")
-        );
+        if self.price_history {
+            price_history.write(PRICE_HISTORY_PATH);
+        }
 
-        let s_set_number = Series::new("set_number", &set_data.set_number);
-        let s_name = Series::new("name", &set_data.name);
-        let s_year = Series::new("year", &set_data.year);
-        let s_retail_price = Series::new("retail_price", &set_data.retail_price);
-        let s_value = Series::new("value", &set_data.value);
-        let s_listed_price = Series::new("listed_price", &set_data.listed_price);
-        let s_pieces = Series::new("pieces", &set_data.pieces);
-
-        let mut df: DataFrame = DataFrame::new(vec![
-            s_set_number,
-            s_name,
-            s_year,
-            s_retail_price,
-            s_value,
-            s_listed_price,
-            s_pieces,
-        ])
-        .expect("A Polars DataFrame.");
+        let mut df: DataFrame = set_data.into_dataframe();
 
         // do everything else, but control the output
         if update_set_list_flag {
-            let mut lf: LazyFrame =
-                df.lazy()
-                    .select(&[col("set_number"), col("year"), col("pieces")]);
+            let mut lf: LazyFrame = df.lazy().select(SET_LIST_COLUMNS.map(col));
             lf = lf
                 // greater than covers nulls
                 .filter(col("pieces").gt(1));
@@ -535,56 +682,96 @@ impl Leget {
                 .expect("An executed LazyFrame for scanned sets.");
 
             // read in the set list
-            let mut set_list_df: DataFrame = CsvReader::from_path("set_list.csv")
-                .expect("A reader connection to set_list.csv")
-                .with_dtypes(Some(&set_list_schema))
-                .has_header(true)
-                .finish()
-                .expect("A polars DataFrame from set_list.csv");
+            let mut set_list_df: DataFrame = read_set_list();
 
             set_list_df
                 .extend(&df)
                 .expect("The scanned df appended to the set_list_df.");
+            // the scan comes last so its barcodes and themes replace the old row's
             set_list_df = set_list_df
-                .unique(Some(&["set_number".to_string()]), UniqueKeepStrategy::First)
+                .unique(Some(&["set_number".to_string()]), UniqueKeepStrategy::Last)
                 .expect("A DataFrame with no duplicate set numbers.")
                 .sort(["set_number"], false)
                 .expect("A asc sorted DataFrame by set number.");
             // TODO: these should probably be behind a --silent flag
             println!("set_list_df: {}", &set_list_df);
 
-            let set_list = File::create("set_list.csv").expect("The creation of the set_list.csv");
-            let mut writer: CsvWriter<File> = CsvWriter::new(set_list).has_header(true);
-            writer
-                .finish(&mut set_list_df)
-                .expect("The writting of our data to set_list.csv");
+            write_set_list(&mut set_list_df);
         } else {
-            let mut lf: LazyFrame = df.lazy();
-            lf = lf
-                .filter(col("listed_price").is_not_null())
-                .filter(col("value").is_not_null())
-                // greater than covers nulls
-                .filter(col("pieces").gt(self.min_pieces))
-                // only for shipping
-                .filter(col("pieces").lt(self.max_pieces))
-                .with_column(
-                    ((col("listed_price") - col("value")) / col("value"))
-                        .alias("percent_discount_from_value"),
-                )
-                // TODO: I would like to not be repeating myself here
-                .with_column(
-                    ((col("listed_price") - col("value")) / (col("value") * col("pieces")))
-                        .alias("percent_discount_from_value_per_piece"),
-                )
-                .sort("percent_discount_from_value_per_piece", Default::default());
+            if !self.skip_history && df.height() > 0 {
+                History::open(HISTORY_DB_PATH).record_scan(&df);
+            }
+
+            self.warn_mixed_currencies();
+            let lf = self.rank(df.lazy()).map_err(|error| eyre!(error))?;
             df = lf.collect().expect("An executed LazyFrame.");
             println!("{}", &df);
 
-            let legot_csv = File::create("legot.csv").expect("The creation of the legot.csv");
-            let mut writer: CsvWriter<File> = CsvWriter::new(legot_csv).has_header(true);
+            if let Some(amount) = self.budget {
+                let budget = Budget {
+                    amount,
+                    objective: self.objective,
+                    max_pieces: self.budget_max_pieces,
+                    max_shipping: self.budget_max_shipping,
+                };
+                let basket = budget.basket(&df, self.shipping);
+                let total = |name: &str| {
+                    basket
+                        .deals
+                        .column(name)
+                        .unwrap_or_else(|_| panic!("The {name} column of the basket."))
+                        .sum::<f64>()
+                        .unwrap_or_default()
+                };
+                println!("basket: {}", &basket.deals);
+                println!(
+                    "{} sets for a landed cost of {:.2} and an expected profit of {:.2}.",
+                    basket.deals.height(),
+                    total("landed_cost"),
+                    total("expected_profit")
+                );
+                if !basket.shortcuts.is_empty() {
+                    println!(
+                        "note: {}, so a better basket may fit the budget.",
+                        basket.shortcuts.join(" and ")
+                    );
+                }
+            }
+
+            if let Some(ref url) = self.webhook_url {
+                let deals = df
+                    .clone()
+                    .lazy()
+                    .filter(
+                        col("percent_discount_from_value")
+                            .lt_eq(lit(-self.notify_threshold / 100.0)),
+                    )
+                    .collect()
+                    .expect("The deals past the notify threshold.");
+                Notifier::new(url, self.webhook_retries)
+                    .notify(&deals)
+                    .await;
+            }
+
+            let legot_csv = if self.append_output {
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&self.output)
+            } else {
+                File::create(&self.output)
+            }
+            .expect("The creation of the output csv");
+            // appended runs only need the header once
+            let has_header = legot_csv
+                .metadata()
+                .expect("The output csv's metadata.")
+                .len()
+                == 0;
+            let mut writer: CsvWriter<File> = CsvWriter::new(legot_csv).has_header(has_header);
             writer
                 .finish(&mut df)
-                .expect("The writting of our data to legot.csv");
+                .expect("The writting of our data to the output csv");
         }
 
         Ok(())
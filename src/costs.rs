@@ -0,0 +1,35 @@
+//! What a set costs us to buy and what it nets us on resale
+
+use polars::prelude::*;
+
+pub struct CostModel {
+    // percents, e.g. 8.25 for 8.25%
+    pub tax_rate: f64,
+    pub fee_rate: f64,
+    // used when there is no shipping_cost estimate for a set, i.e. without
+    // shipping rates or when its weight and box size aren't known
+    pub flat_shipping: f64,
+}
+
+impl CostModel {
+    /// landed_cost, expected_net_resale and expected_profit columns
+    ///
+    /// `has_shipping_cost` is whether a shipping_cost column has been estimated
+    pub fn columns(&self, has_shipping_cost: bool) -> [Expr; 3] {
+        let shipping = if has_shipping_cost {
+            col("shipping_cost").fill_null(lit(self.flat_shipping))
+        } else {
+            lit(self.flat_shipping)
+        };
+
+        let landed_cost = col("listed_price") * lit(1.0 + self.tax_rate / 100.0) + shipping;
+        let expected_net_resale = col("value") * lit(1.0 - self.fee_rate / 100.0);
+        let expected_profit = expected_net_resale.clone() - landed_cost.clone();
+
+        [
+            landed_cost.alias("landed_cost"),
+            expected_net_resale.alias("expected_net_resale"),
+            expected_profit.alias("expected_profit"),
+        ]
+    }
+}
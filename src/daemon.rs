@@ -0,0 +1,182 @@
+//! Scheduling repeated scans for `leget daemon`
+
+use chrono::{Local, NaiveDate};
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use std::str::FromStr;
+use std::time::Duration;
+
+#[derive(Clone)]
+pub enum Schedule {
+    Every(Duration),
+    Cron(Box<cron::Schedule>),
+}
+
+impl Schedule {
+    /// how long to wait before the next scan
+    pub fn until_next(&self) -> Duration {
+        match self {
+            Schedule::Every(interval) => *interval,
+            Schedule::Cron(schedule) => schedule
+                .upcoming(Local)
+                .next()
+                .expect("An upcoming time from the cron expression.")
+                .signed_duration_since(Local::now())
+                .to_std()
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// parse intervals like 90, 90s, 30m, 6h or 1d
+pub fn parse_interval(interval: &str) -> Result<Duration, String> {
+    let split = interval
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(interval.len());
+    let (amount, unit) = interval.split_at(split);
+    let amount: u64 = amount
+        .parse()
+        .map_err(|_| format!("{interval} should start with a whole number"))?;
+    let seconds = match unit {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return Err(format!("{unit} should be one of s, m, h or d")),
+    };
+    if amount == 0 {
+        return Err("the interval should be longer than 0".to_string());
+    }
+
+    Ok(Duration::from_secs(amount * seconds))
+}
+
+pub fn parse_cron(expression: &str) -> Result<Box<cron::Schedule>, String> {
+    cron::Schedule::from_str(expression)
+        .map(Box::new)
+        .map_err(|error| format!("{expression} isn't a valid cron expression: {error}"))
+}
+
+/// the output file for one day's scans, e.g. legot.csv becomes legot-2022-01-31.csv
+pub fn dated_output(output: &str, date: NaiveDate) -> String {
+    let path = Path::new(output);
+    let stem = path
+        .file_stem()
+        .map_or_else(|| output.into(), |stem| stem.to_string_lossy());
+    let file_name = match path.extension() {
+        Some(extension) => format!("{stem}-{date}.{}", extension.to_string_lossy()),
+        None => format!("{stem}-{date}"),
+    };
+
+    path.with_file_name(file_name)
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// the day of a file named like `dated_output` names the files of output
+fn output_date(output: &str, file_name: &str) -> Option<NaiveDate> {
+    let path = Path::new(output);
+    let stem = path.file_stem()?.to_string_lossy();
+    let rest = file_name.strip_prefix(&format!("{stem}-"))?;
+    let date = match path.extension() {
+        Some(extension) => rest.strip_suffix(&format!(".{}", extension.to_string_lossy()))?,
+        None => rest,
+    };
+
+    NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()
+}
+
+/// every day's output file the daemon has written for output, oldest first
+pub fn dated_outputs(output: &str) -> Vec<String> {
+    let path = Path::new(output);
+    let directory = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let mut files: Vec<(NaiveDate, String)> = match fs::read_dir(directory) {
+        Ok(entries) => entries
+            .filter_map(|entry| {
+                let file_name = entry.ok()?.file_name().into_string().ok()?;
+                let date = output_date(output, &file_name)?;
+                Some((
+                    date,
+                    path.with_file_name(file_name)
+                        .to_string_lossy()
+                        .into_owned(),
+                ))
+            })
+            .collect(),
+        Err(_) => vec![],
+    };
+    files.sort();
+
+    files.into_iter().map(|(_, file)| file).collect()
+}
+
+/// resolves once the process is asked to stop
+pub async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut terminate = signal(SignalKind::terminate()).expect("A listener for SIGTERM.");
+        tokio::select! {
+            _ = terminate.recv() => {}
+            _ = tokio::signal::ctrl_c() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c()
+        .await
+        .expect("A listener for ctrl-c.");
+}
+
+/// run a user's command through the shell with the scan's output file in LEGET_OUTPUT
+pub fn run_on_complete(command: &str, output: &str) {
+    #[cfg(unix)]
+    let mut shell = Command::new("sh");
+    #[cfg(unix)]
+    shell.arg("-c");
+    #[cfg(not(unix))]
+    let mut shell = Command::new("cmd");
+    #[cfg(not(unix))]
+    shell.arg("/C");
+
+    match shell.arg(command).env("LEGET_OUTPUT", output).status() {
+        Ok(status) if status.success() => {}
+        Ok(status) => println!("warning: the on-complete command exited with {status}."),
+        Err(error) => println!("warning: the on-complete command couldn't run: {error}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dated_outputs() {
+        let date = NaiveDate::from_ymd_opt(2022, 1, 31).unwrap();
+        assert_eq!(dated_output("legot.csv", date), "legot-2022-01-31.csv");
+        assert_eq!(
+            dated_output("deals/watch.csv", date),
+            "deals/watch-2022-01-31.csv"
+        );
+        assert_eq!(dated_output("deals", date), "deals-2022-01-31");
+        assert_eq!(
+            dated_output("deals.v2.csv", date),
+            "deals.v2-2022-01-31.csv"
+        );
+    }
+
+    #[test]
+    fn output_dates() {
+        let date = NaiveDate::from_ymd_opt(2022, 1, 31);
+        assert_eq!(output_date("legot.csv", "legot-2022-01-31.csv"), date);
+        assert_eq!(output_date("deals/watch.csv", "watch-2022-01-31.csv"), date);
+        assert_eq!(output_date("deals", "deals-2022-01-31"), date);
+        assert_eq!(output_date("deals.csv", "legot-2022-01-31.csv"), None);
+        assert_eq!(output_date("legot.csv", "legot-backup.csv"), None);
+        assert_eq!(output_date("legot.csv", "legot-2022-01-31.json"), None);
+    }
+}
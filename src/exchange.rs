@@ -0,0 +1,188 @@
+//! Converting prices with a local table of exchange rates
+//!
+//! The rates file is a csv of `currency,per_usd,as_of` where `per_usd` is how
+//! much of the currency one US dollar buys, e.g.
+//!
+//! ```text
+//! currency,per_usd,as_of
+//! USD,1.0,2022-11-01
+//! GBP,0.87,2022-11-01
+//! ```
+//!
+//! USD doesn't need a row; one US dollar always buys one.
+
+use crate::money::Currency;
+
+use chrono::NaiveDate;
+use clap::ValueEnum;
+use polars::prelude::*;
+use std::collections::HashMap;
+
+// columns that hold a price in the listing's currency
+const LISTING_PRICE_COLUMNS: [&str; 2] = ["retail_price", "listed_price"];
+// columns that hold a price in US dollars
+const USD_PRICE_COLUMNS: [&str; 2] = ["value", "value_used"];
+
+pub struct ExchangeRates {
+    // each currency's rate and the day it's from
+    per_usd: HashMap<Currency, (f64, NaiveDate)>,
+}
+
+impl ExchangeRates {
+    pub fn from_path(path: &str) -> Self {
+        let mut schema = Schema::new();
+        schema.with_column("currency".to_string(), DataType::Utf8);
+        schema.with_column("per_usd".to_string(), DataType::Float64);
+        schema.with_column("as_of".to_string(), DataType::Utf8);
+
+        let df = CsvReader::from_path(path)
+            .expect("A reader connection to the exchange rates file.")
+            .with_dtypes(Some(&schema))
+            .has_header(true)
+            .finish()
+            .expect("A polars DataFrame from the exchange rates file.");
+
+        let currencies = df
+            .column("currency")
+            .expect("The currency column of the exchange rates.")
+            .utf8()
+            .expect("Currencies as utf8.");
+        let per_usd = df
+            .column("per_usd")
+            .expect("The per_usd column of the exchange rates.")
+            .f64()
+            .expect("Rates as f64.");
+        let as_of = df
+            .column("as_of")
+            .expect("The as_of column of the exchange rates.")
+            .utf8()
+            .expect("As of dates as utf8.");
+
+        let mut rates = ExchangeRates {
+            per_usd: HashMap::new(),
+        };
+        for ((currency, rate), date) in currencies
+            .into_no_null_iter()
+            .zip(per_usd.into_no_null_iter())
+            .zip(as_of.into_no_null_iter())
+        {
+            let currency = Currency::from_str(currency, true)
+                .unwrap_or_else(|_| panic!("{currency} isn't a supported currency."));
+            let date = NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                .unwrap_or_else(|_| panic!("{date} as a YYYY-MM-DD date."));
+            rates.per_usd.insert(currency, (rate, date));
+        }
+        assert!(
+            !rates.per_usd.is_empty(),
+            "The exchange rates file {path} has no rates."
+        );
+
+        rates
+    }
+
+    // how much of `currency` a US dollar buys and, unless it's the dollar
+    // itself, the day of that rate
+    fn rate(&self, currency: Currency) -> (f64, Option<NaiveDate>) {
+        match self.per_usd.get(&currency) {
+            Some(&(rate, date)) => (rate, Some(date)),
+            None if currency == Currency::Usd => (1.0, None),
+            None => panic!(
+                "No exchange rate for {} in the rates file.",
+                currency.code()
+            ),
+        }
+    }
+
+    fn factor(&self, from: Currency, to: Currency) -> f64 {
+        self.rate(to).0 / self.rate(from).0
+    }
+
+    /// convert every price column to `to` and record the rates used
+    pub fn convert(&self, lf: LazyFrame, from: Currency, to: Currency) -> LazyFrame {
+        let listing_factor = self.factor(from, to);
+        let usd_factor = self.factor(Currency::Usd, to);
+
+        let mut conversions: Vec<Expr> = LISTING_PRICE_COLUMNS
+            .iter()
+            .map(|column| col(column) * lit(listing_factor))
+            .collect();
+        conversions.extend(
+            USD_PRICE_COLUMNS
+                .iter()
+                .map(|column| col(column) * lit(usd_factor)),
+        );
+        conversions.push(lit(to.code()).alias("currency"));
+        // the oldest of the rates used, none when only dollars were involved
+        let as_of = [from, to, Currency::Usd]
+            .into_iter()
+            .filter_map(|currency| self.rate(currency).1)
+            .min();
+        conversions.push(
+            as_of
+                .map_or_else(|| lit(NULL), lit)
+                .cast(DataType::Date)
+                .alias("rates_as_of"),
+        );
+
+        lf.with_columns(conversions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rates() -> ExchangeRates {
+        let day = |day| NaiveDate::from_ymd_opt(2022, 11, day).unwrap();
+        ExchangeRates {
+            per_usd: HashMap::from([
+                (Currency::Gbp, (0.5, day(1))),
+                (Currency::Eur, (1.25, day(3))),
+                (Currency::Cad, (1.3, day(2))),
+            ]),
+        }
+    }
+
+    #[test]
+    fn factors() {
+        let rates = rates();
+        assert_eq!(rates.factor(Currency::Usd, Currency::Gbp), 0.5);
+        assert_eq!(rates.factor(Currency::Gbp, Currency::Usd), 2.0);
+        assert_eq!(rates.factor(Currency::Gbp, Currency::Eur), 2.5);
+        assert_eq!(rates.factor(Currency::Eur, Currency::Eur), 1.0);
+        assert_eq!(rates.factor(Currency::Usd, Currency::Usd), 1.0);
+    }
+
+    #[test]
+    fn conversions_record_the_rates_used() {
+        let rates = rates();
+        let df = df!(
+            "retail_price" => [10.0],
+            "listed_price" => [8.0],
+            "value" => [20.0],
+            "value_used" => [15.0]
+        )
+        .unwrap();
+        let convert = |from, to| {
+            rates
+                .convert(df.clone().lazy(), from, to)
+                .collect()
+                .unwrap()
+        };
+        let value = |df: &DataFrame, name: &str| df.column(name).unwrap().get(0).to_string();
+
+        let df = convert(Currency::Gbp, Currency::Eur);
+        assert_eq!(value(&df, "listed_price"), "20.0");
+        assert_eq!(value(&df, "value"), "25.0");
+        assert_eq!(value(&df, "currency"), "\"EUR\"");
+        // CAD's older rate wasn't used
+        assert_eq!(value(&df, "rates_as_of"), "2022-11-01");
+
+        let df = convert(Currency::Usd, Currency::Eur);
+        assert_eq!(value(&df, "rates_as_of"), "2022-11-03");
+
+        let df = convert(Currency::Usd, Currency::Usd);
+        assert_eq!(value(&df, "retail_price"), "10.0");
+        assert_eq!(value(&df, "rates_as_of"), "null");
+    }
+}
@@ -0,0 +1,813 @@
+//! A small expression language over the output columns
+//!
+//! e.g. `(value - listed_price) / pieces * 100` for a score or
+//! `value > 100 and theme = "Technic"` for a filter
+//!
+//! expressions are parsed when the arguments are and only checked against
+//! the output columns once we know what they are
+
+use polars::prelude::*;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+}
+
+impl Operator {
+    fn apply(self, left: Expr, right: Expr) -> Expr {
+        match self {
+            Operator::Add => left + right,
+            Operator::Subtract => left - right,
+            Operator::Multiply => left * right,
+            Operator::Divide => left / right,
+        }
+    }
+
+    fn symbol(self) -> &'static str {
+        match self {
+            Operator::Add => "+",
+            Operator::Subtract => "-",
+            Operator::Multiply => "*",
+            Operator::Divide => "/",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl Comparison {
+    fn apply(self, left: Expr, right: Expr) -> Expr {
+        match self {
+            Comparison::Equal => left.eq(right),
+            Comparison::NotEqual => left.neq(right),
+            Comparison::Less => left.lt(right),
+            Comparison::LessOrEqual => left.lt_eq(right),
+            Comparison::Greater => left.gt(right),
+            Comparison::GreaterOrEqual => left.gt_eq(right),
+        }
+    }
+}
+
+/// a parsed expression that hasn't been checked against any columns yet
+#[derive(Clone, Debug, PartialEq)]
+pub enum Ast {
+    Number(f64),
+    Text(String),
+    Column(String),
+    Negate(Box<Ast>),
+    Binary(Box<Ast>, Operator, Box<Ast>),
+    Compare(Box<Ast>, Comparison, Box<Ast>),
+    Not(Box<Ast>),
+    And(Box<Ast>, Box<Ast>),
+    Or(Box<Ast>, Box<Ast>),
+}
+
+// what an expression gives for each row, so mistakes are caught before
+// polars runs it over the scraped sets
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Kind {
+    Number,
+    Text,
+    Boolean,
+    Date,
+    Other,
+}
+
+impl Kind {
+    fn of(dtype: &DataType) -> Self {
+        match dtype {
+            DataType::Boolean => Kind::Boolean,
+            DataType::Utf8 => Kind::Text,
+            DataType::Date | DataType::Datetime(_, _) => Kind::Date,
+            dtype if dtype.is_numeric() => Kind::Number,
+            _ => Kind::Other,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Kind::Number => "a number",
+            Kind::Text => "text",
+            Kind::Boolean => "true or false",
+            Kind::Date => "a date",
+            Kind::Other => "an unsupported type",
+        }
+    }
+}
+
+impl Ast {
+    /// a polars expression that's true or false for every set, or an error
+    /// naming the first unknown column or mismatched types
+    pub fn to_filter(&self, schema: &Schema) -> Result<Expr, String> {
+        match self.typed(schema)? {
+            (expr, Kind::Boolean) => Ok(expr),
+            (_, kind) => Err(format!(
+                "the filter should be true or false, not {}",
+                kind.name()
+            )),
+        }
+    }
+
+    /// a polars expression that gives a number for every set
+    pub fn to_score(&self, schema: &Schema) -> Result<Expr, String> {
+        match self.typed(schema)? {
+            (expr, Kind::Number) => Ok(expr),
+            (_, kind) => Err(format!("the score should be a number, not {}", kind.name())),
+        }
+    }
+
+    fn typed(&self, schema: &Schema) -> Result<(Expr, Kind), String> {
+        Ok(match self {
+            Ast::Number(number) => (lit(*number), Kind::Number),
+            Ast::Text(text) => (lit(text.as_str()), Kind::Text),
+            Ast::Column(name) => match schema.get(name) {
+                Some(dtype) => (col(name), Kind::of(dtype)),
+                None => {
+                    let columns: Vec<&str> =
+                        schema.iter_names().map(|name| name.as_str()).collect();
+                    return Err(format!(
+                        "there's no {name} column. the columns are: {}",
+                        columns.join(", ")
+                    ));
+                }
+            },
+            Ast::Negate(ast) => (lit(0.0) - ast.number(schema, "-")?, Kind::Number),
+            Ast::Binary(left, operator, right) => (
+                operator.apply(
+                    left.number(schema, operator.symbol())?,
+                    right.number(schema, operator.symbol())?,
+                ),
+                Kind::Number,
+            ),
+            Ast::Compare(left, comparison, right) => {
+                let (left, left_kind) = left.typed(schema)?;
+                let (right, right_kind) = right.typed(schema)?;
+                if left_kind != right_kind || left_kind == Kind::Other {
+                    return Err(format!(
+                        "can't compare {} with {}",
+                        left_kind.name(),
+                        right_kind.name()
+                    ));
+                }
+                (comparison.apply(left, right), Kind::Boolean)
+            }
+            Ast::Not(ast) => (ast.boolean(schema, "not")?.not(), Kind::Boolean),
+            Ast::And(left, right) => (
+                left.boolean(schema, "and")?
+                    .and(right.boolean(schema, "and")?),
+                Kind::Boolean,
+            ),
+            Ast::Or(left, right) => (
+                left.boolean(schema, "or")?.or(right.boolean(schema, "or")?),
+                Kind::Boolean,
+            ),
+        })
+    }
+
+    // the expression of an operand of `keyword`, which has to be true or false
+    fn boolean(&self, schema: &Schema, keyword: &str) -> Result<Expr, String> {
+        match self.typed(schema)? {
+            (expr, Kind::Boolean) => Ok(expr),
+            (_, kind) => Err(format!(
+                "{keyword} needs true or false, not {}",
+                kind.name()
+            )),
+        }
+    }
+
+    // the expression of an operand of `operator`, which has to be a number
+    fn number(&self, schema: &Schema, operator: &str) -> Result<Expr, String> {
+        match self.typed(schema)? {
+            (expr, Kind::Number) => Ok(expr),
+            (_, kind) => Err(format!("{operator} needs numbers, not {}", kind.name())),
+        }
+    }
+
+    /// the parts of a filter that only use columns in `schema`, joined by and
+    ///
+    /// a row the whole filter keeps is always kept by this, so it can narrow
+    /// down data that doesn't have every column yet, e.g. the set list. rows
+    /// with nulls are kept too since scraping may fill them in.
+    pub fn prefilter(&self, schema: &Schema) -> Option<Expr> {
+        self.conjuncts()
+            .into_iter()
+            .filter(|ast| ast.columns().iter().all(|name| schema.get(name).is_some()))
+            .filter_map(|ast| {
+                let expr = ast.to_filter(schema).ok()?;
+                // comparisons with a null are false rather than null
+                let has_null = ast
+                    .columns()
+                    .into_iter()
+                    .map(|name| col(name).is_null())
+                    .reduce(|left, right| left.or(right));
+                Some(match has_null {
+                    Some(has_null) => expr.or(has_null),
+                    None => expr,
+                })
+            })
+            .reduce(|left, right| left.and(right))
+    }
+
+    // the parts of a chain of ands
+    fn conjuncts(&self) -> Vec<&Ast> {
+        match self {
+            Ast::And(left, right) => {
+                let mut conjuncts = left.conjuncts();
+                conjuncts.extend(right.conjuncts());
+                conjuncts
+            }
+            ast => vec![ast],
+        }
+    }
+
+    fn columns(&self) -> Vec<&str> {
+        match self {
+            Ast::Number(_) | Ast::Text(_) => vec![],
+            Ast::Column(name) => vec![name.as_str()],
+            Ast::Negate(ast) | Ast::Not(ast) => ast.columns(),
+            Ast::Binary(left, _, right)
+            | Ast::Compare(left, _, right)
+            | Ast::And(left, right)
+            | Ast::Or(left, right) => {
+                let mut columns = left.columns();
+                columns.extend(right.columns());
+                columns
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(f64),
+    Text(String),
+    Name(String),
+    Operator(Operator),
+    Comparison(Comparison),
+    Open,
+    Close,
+}
+
+/// parse an expression like `(value - listed_price) / pieces * 100`
+pub fn parse(text: &str) -> Result<Ast, String> {
+    let mut parser = Parser {
+        tokens: tokenize(text)?,
+        position: 0,
+        end: text.len(),
+    };
+    let ast = parser.or()?;
+    match parser.tokens.get(parser.position) {
+        None => Ok(ast),
+        Some((at, _)) => Err(format!("unexpected text at position {at}")),
+    }
+}
+
+// tokens along with where in the text they start
+fn tokenize(text: &str) -> Result<Vec<(usize, Token)>, String> {
+    let mut tokens = vec![];
+    let mut chars = text.char_indices().peekable();
+
+    while let Some(&(at, c)) = chars.peek() {
+        let token = match c {
+            c if c.is_whitespace() => {
+                chars.next();
+                continue;
+            }
+            '0'..='9' | '.' => {
+                let mut number = String::new();
+                while let Some(&(_, c)) = chars.peek() {
+                    if !(c.is_ascii_digit() || c == '.') {
+                        break;
+                    }
+                    number.push(c);
+                    chars.next();
+                }
+                let number = number
+                    .parse::<f64>()
+                    .map_err(|_| format!("{number} at position {at} isn't a number"))?;
+                tokens.push((at, Token::Number(number)));
+                continue;
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut name = String::new();
+                while let Some(&(_, c)) = chars.peek() {
+                    if !(c.is_alphanumeric() || c == '_') {
+                        break;
+                    }
+                    name.push(c);
+                    chars.next();
+                }
+                tokens.push((at, Token::Name(name)));
+                continue;
+            }
+            '"' | '\'' => {
+                let quote = c;
+                chars.next();
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, c)) if c == quote => break,
+                        Some((_, c)) => text.push(c),
+                        None => return Err(format!("the text at position {at} isn't closed")),
+                    }
+                }
+                tokens.push((at, Token::Text(text)));
+                continue;
+            }
+            '=' | '!' | '<' | '>' => {
+                chars.next();
+                let or_equal = chars.next_if(|&(_, c)| c == '=').is_some();
+                let comparison = match (c, or_equal) {
+                    ('=', _) => Comparison::Equal,
+                    ('!', true) => Comparison::NotEqual,
+                    ('<', false) => Comparison::Less,
+                    ('<', true) => Comparison::LessOrEqual,
+                    ('>', false) => Comparison::Greater,
+                    ('>', true) => Comparison::GreaterOrEqual,
+                    _ => return Err(format!("expected != at position {at}")),
+                };
+                tokens.push((at, Token::Comparison(comparison)));
+                continue;
+            }
+            '+' => Token::Operator(Operator::Add),
+            '-' => Token::Operator(Operator::Subtract),
+            '*' => Token::Operator(Operator::Multiply),
+            '/' => Token::Operator(Operator::Divide),
+            '(' => Token::Open,
+            ')' => Token::Close,
+            c => return Err(format!("unexpected {c} at position {at}")),
+        };
+        tokens.push((at, token));
+        chars.next();
+    }
+
+    Ok(tokens)
+}
+
+// a recursive descent parser, one method per level of precedence
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    position: usize,
+    // where to say a missing token was expected
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(_, token)| token)
+    }
+
+    fn at(&self) -> usize {
+        self.tokens
+            .get(self.position)
+            .map_or(self.end, |(at, _)| *at)
+    }
+
+    // the next operator if it's one of `operators`
+    fn operator(&mut self, operators: &[Operator]) -> Option<Operator> {
+        match self.peek() {
+            Some(Token::Operator(operator)) if operators.contains(operator) => {
+                let operator = *operator;
+                self.position += 1;
+                Some(operator)
+            }
+            _ => None,
+        }
+    }
+
+    // whether the next token is the keyword, and skip it if it is
+    fn keyword(&mut self, keyword: &str) -> bool {
+        match self.peek() {
+            Some(Token::Name(name)) if name.eq_ignore_ascii_case(keyword) => {
+                self.position += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // or := and ('or' and)*
+    fn or(&mut self) -> Result<Ast, String> {
+        let mut ast = self.and()?;
+        while self.keyword("or") {
+            ast = Ast::Or(Box::new(ast), Box::new(self.and()?));
+        }
+        Ok(ast)
+    }
+
+    // and := not ('and' not)*
+    fn and(&mut self) -> Result<Ast, String> {
+        let mut ast = self.not()?;
+        while self.keyword("and") {
+            ast = Ast::And(Box::new(ast), Box::new(self.not()?));
+        }
+        Ok(ast)
+    }
+
+    // not := 'not' not | comparison
+    fn not(&mut self) -> Result<Ast, String> {
+        if self.keyword("not") {
+            return Ok(Ast::Not(Box::new(self.not()?)));
+        }
+        self.comparison()
+    }
+
+    // comparison := sum (('=' | '!=' | '<' | '<=' | '>' | '>=') sum)?
+    fn comparison(&mut self) -> Result<Ast, String> {
+        let ast = self.sum()?;
+        if let Some(Token::Comparison(comparison)) = self.peek() {
+            let comparison = *comparison;
+            self.position += 1;
+            return Ok(Ast::Compare(
+                Box::new(ast),
+                comparison,
+                Box::new(self.sum()?),
+            ));
+        }
+        Ok(ast)
+    }
+
+    // sum := product (('+' | '-') product)*
+    fn sum(&mut self) -> Result<Ast, String> {
+        let mut ast = self.product()?;
+        while let Some(operator) = self.operator(&[Operator::Add, Operator::Subtract]) {
+            ast = Ast::Binary(Box::new(ast), operator, Box::new(self.product()?));
+        }
+        Ok(ast)
+    }
+
+    // product := unary (('*' | '/') unary)*
+    fn product(&mut self) -> Result<Ast, String> {
+        let mut ast = self.unary()?;
+        while let Some(operator) = self.operator(&[Operator::Multiply, Operator::Divide]) {
+            ast = Ast::Binary(Box::new(ast), operator, Box::new(self.unary()?));
+        }
+        Ok(ast)
+    }
+
+    // unary := '-' unary | primary
+    fn unary(&mut self) -> Result<Ast, String> {
+        if self.operator(&[Operator::Subtract]).is_some() {
+            return Ok(Ast::Negate(Box::new(self.unary()?)));
+        }
+        self.primary()
+    }
+
+    // primary := number | text | column | '(' or ')'
+    fn primary(&mut self) -> Result<Ast, String> {
+        let at = self.at();
+        let ast = match self.peek().cloned() {
+            Some(Token::Number(number)) => Ast::Number(number),
+            Some(Token::Text(text)) => Ast::Text(text),
+            Some(Token::Name(name)) => Ast::Column(name),
+            Some(Token::Open) => {
+                self.position += 1;
+                let ast = self.or()?;
+                if self.peek() != Some(&Token::Close) {
+                    return Err(format!("expected ) at position {}", self.at()));
+                }
+                ast
+            }
+            _ => {
+                return Err(format!(
+                    "expected a column, number, text or ( at position {at}"
+                ))
+            }
+        };
+        self.position += 1;
+        Ok(ast)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn column(name: &str) -> Box<Ast> {
+        Box::new(Ast::Column(name.to_string()))
+    }
+
+    fn number(number: f64) -> Box<Ast> {
+        Box::new(Ast::Number(number))
+    }
+
+    fn compare(name: &str, comparison: Comparison, value: f64) -> Box<Ast> {
+        Box::new(Ast::Compare(column(name), comparison, number(value)))
+    }
+
+    fn sets() -> DataFrame {
+        df!(
+            "set_number" => ["1-1", "2-1", "3-1", "4-1", "5-1", "6-1"],
+            "theme" => [Some("City"), Some("City"), Some("Technic"), None, Some("City"), Some("City")],
+            "year" => [Some(2021), Some(2019), Some(2022), Some(2022), None, Some(2023)],
+            "pieces" => [Some(600u32), Some(800), Some(1200), Some(700), Some(550), Some(300)],
+            "value" => [150.0, 120.0, 300.0, 200.0, 90.0, 400.0],
+            "listed_price" => [100.0, 100.0, 250.0, 150.0, 80.0, 300.0],
+            "score" => [0.5, -0.1, 0.2, 0.3, 0.1, 0.4]
+        )
+        .unwrap()
+    }
+
+    fn set_numbers(df: DataFrame, filter: Expr) -> Vec<String> {
+        df.lazy()
+            .filter(filter)
+            .collect()
+            .unwrap()
+            .column("set_number")
+            .unwrap()
+            .utf8()
+            .unwrap()
+            .into_no_null_iter()
+            .map(String::from)
+            .collect()
+    }
+
+    #[test]
+    fn arithmetic_precedence() {
+        assert_eq!(
+            parse("1 + 2 * 3").unwrap(),
+            Ast::Binary(
+                number(1.0),
+                Operator::Add,
+                Box::new(Ast::Binary(number(2.0), Operator::Multiply, number(3.0)))
+            )
+        );
+        assert_eq!(
+            parse("(value - listed_price) / pieces").unwrap(),
+            Ast::Binary(
+                Box::new(Ast::Binary(
+                    column("value"),
+                    Operator::Subtract,
+                    column("listed_price")
+                )),
+                Operator::Divide,
+                column("pieces")
+            )
+        );
+        // left associative
+        assert_eq!(
+            parse("8 / 4 / 2").unwrap(),
+            Ast::Binary(
+                Box::new(Ast::Binary(number(8.0), Operator::Divide, number(4.0))),
+                Operator::Divide,
+                number(2.0)
+            )
+        );
+        assert_eq!(
+            parse("-value - -2").unwrap(),
+            Ast::Binary(
+                Box::new(Ast::Negate(column("value"))),
+                Operator::Subtract,
+                Box::new(Ast::Negate(number(2.0)))
+            )
+        );
+    }
+
+    #[test]
+    fn boolean_precedence() {
+        // not binds tighter than and, which binds tighter than or
+        assert_eq!(
+            parse("year = 2021 or year = 2022 and not pieces < 500").unwrap(),
+            Ast::Or(
+                compare("year", Comparison::Equal, 2021.0),
+                Box::new(Ast::And(
+                    compare("year", Comparison::Equal, 2022.0),
+                    Box::new(Ast::Not(compare("pieces", Comparison::Less, 500.0)))
+                ))
+            )
+        );
+        assert_eq!(
+            parse("(year = 2021 OR year = 2022) AND pieces > 500").unwrap(),
+            Ast::And(
+                Box::new(Ast::Or(
+                    compare("year", Comparison::Equal, 2021.0),
+                    compare("year", Comparison::Equal, 2022.0)
+                )),
+                compare("pieces", Comparison::Greater, 500.0)
+            )
+        );
+        // comparisons bind looser than arithmetic
+        assert_eq!(
+            parse("value - listed_price > 10").unwrap(),
+            Ast::Compare(
+                Box::new(Ast::Binary(
+                    column("value"),
+                    Operator::Subtract,
+                    column("listed_price")
+                )),
+                Comparison::Greater,
+                number(10.0)
+            )
+        );
+    }
+
+    #[test]
+    fn comparisons_and_text() {
+        for (text, comparison) in [
+            ("a = 1", Comparison::Equal),
+            ("a != 1", Comparison::NotEqual),
+            ("a < 1", Comparison::Less),
+            ("a <= 1", Comparison::LessOrEqual),
+            ("a > 1", Comparison::Greater),
+            ("a >= 1", Comparison::GreaterOrEqual),
+            ("a>=1", Comparison::GreaterOrEqual),
+        ] {
+            assert_eq!(
+                parse(text).unwrap(),
+                *compare("a", comparison, 1.0),
+                "{text}"
+            );
+        }
+        assert_eq!(
+            parse(r#"theme != "Star Wars""#).unwrap(),
+            Ast::Compare(
+                column("theme"),
+                Comparison::NotEqual,
+                Box::new(Ast::Text("Star Wars".to_string()))
+            )
+        );
+        assert_eq!(
+            parse(r#"name = 'Santa"s Workshop'"#).unwrap(),
+            Ast::Compare(
+                column("name"),
+                Comparison::Equal,
+                Box::new(Ast::Text("Santa\"s Workshop".to_string()))
+            )
+        );
+        assert_eq!(parse("0.5").unwrap(), Ast::Number(0.5));
+        assert_eq!(parse(".5").unwrap(), Ast::Number(0.5));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(
+            parse("(value - listed_price").unwrap_err(),
+            "expected ) at position 21"
+        );
+        assert_eq!(
+            parse("value +").unwrap_err(),
+            "expected a column, number, text or ( at position 7"
+        );
+        assert_eq!(parse("value ! 1").unwrap_err(), "expected != at position 6");
+        assert_eq!(
+            parse("theme = 'City").unwrap_err(),
+            "the text at position 8 isn't closed"
+        );
+        assert_eq!(
+            parse("1..2").unwrap_err(),
+            "1..2 at position 0 isn't a number"
+        );
+        assert_eq!(
+            parse("value % 2").unwrap_err(),
+            "unexpected % at position 6"
+        );
+        assert_eq!(
+            parse("value pieces").unwrap_err(),
+            "unexpected text at position 6"
+        );
+        assert!(parse("").is_err());
+    }
+
+    #[test]
+    fn unknown_columns() {
+        let schema = sets().schema();
+        let error = parse("(value - price) / pieces")
+            .unwrap()
+            .to_score(&schema)
+            .unwrap_err();
+        assert_eq!(
+            error,
+            "there's no price column. the columns are: set_number, theme, year, pieces, value, listed_price, score"
+        );
+        assert!(parse("not colour = 'red'")
+            .unwrap()
+            .to_filter(&schema)
+            .unwrap_err()
+            .starts_with("there's no colour column."));
+    }
+
+    #[test]
+    fn arithmetic_needs_numbers() {
+        let schema = sets().schema();
+        assert_eq!(
+            parse("theme + 1").unwrap().to_score(&schema).unwrap_err(),
+            "+ needs numbers, not text"
+        );
+        assert_eq!(
+            parse("-theme").unwrap().to_score(&schema).unwrap_err(),
+            "- needs numbers, not text"
+        );
+        assert_eq!(
+            parse("(value > 1) * 2")
+                .unwrap()
+                .to_score(&schema)
+                .unwrap_err(),
+            "* needs numbers, not true or false"
+        );
+        assert_eq!(
+            parse("theme").unwrap().to_score(&schema).unwrap_err(),
+            "the score should be a number, not text"
+        );
+        assert!(parse("pieces * 2 - year / 3")
+            .unwrap()
+            .to_score(&schema)
+            .is_ok());
+    }
+
+    #[test]
+    fn filters_need_matching_types() {
+        let schema = sets().schema();
+        let error = |text: &str| parse(text).unwrap().to_filter(&schema).unwrap_err();
+        assert_eq!(
+            error(r#"year = "2021""#),
+            "can't compare a number with text"
+        );
+        assert_eq!(error("theme > 3"), "can't compare text with a number");
+        assert_eq!(
+            error("theme"),
+            "the filter should be true or false, not text"
+        );
+        assert_eq!(
+            error("value - listed_price"),
+            "the filter should be true or false, not a number"
+        );
+        assert_eq!(
+            error("value > 1 and pieces"),
+            "and needs true or false, not a number"
+        );
+        assert_eq!(error("not theme"), "not needs true or false, not text");
+        assert_eq!(
+            error("year = 2021 or 'City'"),
+            "or needs true or false, not text"
+        );
+
+        // the prefilter leaves out parts that don't type check
+        let prefilter = parse(r#"year = "2021" and theme = "City""#)
+            .unwrap()
+            .prefilter(&schema)
+            .unwrap();
+        assert_eq!(set_numbers(sets(), prefilter).len(), 5);
+    }
+
+    #[test]
+    fn expressions_evaluate() {
+        let df = sets();
+        let filter = parse(r#"theme = "City" and (value - listed_price) / value >= 0.25"#)
+            .unwrap()
+            .to_filter(&df.schema())
+            .unwrap();
+        assert_eq!(set_numbers(df, filter), vec!["1-1", "6-1"]);
+
+        let score = parse("-(value - listed_price) * 2")
+            .unwrap()
+            .to_score(&sets().schema())
+            .unwrap();
+        let scores = sets().lazy().select([score]).collect().unwrap();
+        assert_eq!(scores[0].f64().unwrap().get(0), Some(-100.0));
+    }
+
+    #[test]
+    fn prefilter_keeps_everything_the_filter_does() {
+        let filter = parse(
+            r#"theme = "City" and value > 100 and (year >= 2020 or score > 0) and not pieces < 500"#,
+        )
+        .unwrap();
+        let full = sets();
+        let kept = set_numbers(full.clone(), filter.to_filter(&full.schema()).unwrap());
+        assert_eq!(kept, vec!["1-1"]);
+
+        // like the set list, before there's a value or score
+        let set_list = full.drop("value").unwrap().drop("score").unwrap();
+        let prefilter = filter.prefilter(&set_list.schema()).unwrap();
+        let prekept = set_numbers(set_list, prefilter);
+        // 3-1 is the wrong theme and 6-1 too small, but a null theme or year
+        // could still match once scraped
+        assert_eq!(prekept, vec!["1-1", "2-1", "4-1", "5-1"]);
+        assert!(kept.iter().all(|set_number| prekept.contains(set_number)));
+    }
+
+    #[test]
+    fn prefilter_without_usable_parts() {
+        let set_list = sets().drop("value").unwrap().drop("score").unwrap();
+        let schema = set_list.schema();
+        assert!(parse("value > 100 or year = 2021")
+            .unwrap()
+            .prefilter(&schema)
+            .is_none());
+        assert!(parse("score > 0 and not value < 10")
+            .unwrap()
+            .prefilter(&schema)
+            .is_none());
+    }
+}
@@ -0,0 +1,335 @@
+//! A local SQLite record of every scan
+//!
+//! Each run of a scan gets a row in `scans` and every set it scraped gets a row
+//! in `records` pointing back at it, so prices can be compared over time.
+
+use chrono::{Local, NaiveDate, NaiveDateTime};
+use polars::prelude::*;
+use rusqlite::{params, Connection};
+
+pub const HISTORY_DB_PATH: &str = "leget.db";
+
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// which earlier record each set's latest record is compared to
+#[derive(Clone, Copy, Debug)]
+pub enum Since {
+    // the set's previous record
+    Last,
+    // the set's last record on or before the date
+    Date(NaiveDate),
+}
+
+impl std::str::FromStr for Since {
+    type Err = String;
+
+    fn from_str(since: &str) -> Result<Self, Self::Err> {
+        if since == "last" {
+            Ok(Since::Last)
+        } else {
+            NaiveDate::parse_from_str(since, "%Y-%m-%d")
+                .map(Since::Date)
+                .map_err(|_| format!("{since} should be 'last' or a YYYY-MM-DD date"))
+        }
+    }
+}
+
+pub struct History {
+    connection: Connection,
+}
+
+impl History {
+    pub fn open(path: &str) -> Self {
+        let connection = Connection::open(path).expect("A connection to the history database.");
+        connection
+            .execute_batch(
+                "CREATE TABLE IF NOT EXISTS scans (
+                    id INTEGER PRIMARY KEY,
+                    scanned_at TEXT NOT NULL
+                );
+                CREATE TABLE IF NOT EXISTS records (
+                    scan_id INTEGER NOT NULL REFERENCES scans(id),
+                    set_number TEXT NOT NULL,
+                    name TEXT,
+                    year INTEGER,
+                    pieces INTEGER,
+                    retail_price REAL,
+                    value REAL,
+                    listed_price REAL,
+                    currency TEXT,
+                    region TEXT
+                );
+                CREATE INDEX IF NOT EXISTS records_set_number ON records(set_number);
+                CREATE TABLE IF NOT EXISTS notifications (
+                    set_number TEXT NOT NULL,
+                    listed_price REAL NOT NULL,
+                    notified_at TEXT NOT NULL,
+                    PRIMARY KEY (set_number, listed_price)
+                );",
+            )
+            .expect("The history tables.");
+
+        History { connection }
+    }
+
+    /// append every row of a scan's DataFrame and return the new scan id
+    pub fn record_scan(&mut self, df: &DataFrame) -> i64 {
+        let utf8 = |name: &str| {
+            df.column(name)
+                .unwrap_or_else(|_| panic!("The {name} column."))
+                .utf8()
+                .unwrap_or_else(|_| panic!("{name} as utf8."))
+                .clone()
+        };
+        let f64 = |name: &str| {
+            df.column(name)
+                .unwrap_or_else(|_| panic!("The {name} column."))
+                .f64()
+                .unwrap_or_else(|_| panic!("{name} as f64."))
+                .clone()
+        };
+        let set_numbers = utf8("set_number");
+        let names = utf8("name");
+        let years = df
+            .column("year")
+            .expect("The year column.")
+            .i32()
+            .expect("year as i32.")
+            .clone();
+        let pieces = df
+            .column("pieces")
+            .expect("The pieces column.")
+            .u32()
+            .expect("pieces as u32.")
+            .clone();
+        let retail_prices = f64("retail_price");
+        let values = f64("value");
+        let listed_prices = f64("listed_price");
+        let currencies = utf8("currency");
+        let regions = utf8("region");
+
+        let transaction = self
+            .connection
+            .transaction()
+            .expect("A transaction on the history database.");
+        transaction
+            .execute(
+                "INSERT INTO scans (scanned_at) VALUES (?1)",
+                params![Local::now().format(TIMESTAMP_FORMAT).to_string()],
+            )
+            .expect("A new scan in the history database.");
+        let scan_id = transaction.last_insert_rowid();
+
+        {
+            let mut insert = transaction
+                .prepare(
+                    "INSERT INTO records (scan_id, set_number, name, year, pieces, retail_price, value, listed_price, currency, region)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+                )
+                .expect("A prepared insert of scan records.");
+            for row in 0..df.height() {
+                insert
+                    .execute(params![
+                        scan_id,
+                        set_numbers.get(row),
+                        names.get(row),
+                        years.get(row),
+                        pieces.get(row),
+                        retail_prices.get(row),
+                        values.get(row),
+                        listed_prices.get(row),
+                        currencies.get(row),
+                        regions.get(row),
+                    ])
+                    .expect("A scan record in the history database.");
+            }
+        }
+        transaction
+            .commit()
+            .expect("The scan committed to the history database.");
+
+        scan_id
+    }
+
+    /// whether a deal on a set at this price has already been sent
+    pub fn was_notified(&self, set_number: &str, listed_price: f64) -> bool {
+        self.connection
+            .query_row(
+                "SELECT EXISTS (SELECT 1 FROM notifications WHERE set_number = ?1 AND listed_price = ?2)",
+                params![set_number, listed_price],
+                |row| row.get(0),
+            )
+            .expect("A lookup of sent notifications.")
+    }
+
+    pub fn record_notification(&mut self, set_number: &str, listed_price: f64) {
+        self.connection
+            .execute(
+                "INSERT OR IGNORE INTO notifications (set_number, listed_price, notified_at) VALUES (?1, ?2, ?3)",
+                params![
+                    set_number,
+                    listed_price,
+                    Local::now().format(TIMESTAMP_FORMAT).to_string()
+                ],
+            )
+            .expect("A sent notification in the history database.");
+    }
+
+    /// every recorded value and listed price of a set, oldest first, with the
+    /// currency and region each was recorded in
+    pub fn set_history(&self, set_number: &str) -> DataFrame {
+        let mut statement = self
+            .connection
+            .prepare(
+                "SELECT scans.id, scans.scanned_at, records.value, records.listed_price, records.retail_price,
+                    records.currency, records.region
+                FROM records JOIN scans ON scans.id = records.scan_id
+                WHERE records.set_number = ?1
+                ORDER BY scans.id",
+            )
+            .expect("A prepared query of a set's history.");
+
+        let mut scan_id: Vec<i64> = vec![];
+        let mut scanned_at: Vec<NaiveDateTime> = vec![];
+        let mut value: Vec<Option<f64>> = vec![];
+        let mut listed_price: Vec<Option<f64>> = vec![];
+        let mut retail_price: Vec<Option<f64>> = vec![];
+        let mut currency: Vec<Option<String>> = vec![];
+        let mut region: Vec<Option<String>> = vec![];
+
+        let rows = statement
+            .query_map(params![set_number], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, Option<f64>>(2)?,
+                    row.get::<_, Option<f64>>(3)?,
+                    row.get::<_, Option<f64>>(4)?,
+                    row.get::<_, Option<String>>(5)?,
+                    row.get::<_, Option<String>>(6)?,
+                ))
+            })
+            .expect("The rows of a set's history.");
+        for row in rows {
+            let row = row.expect("A row of a set's history.");
+            scan_id.push(row.0);
+            scanned_at.push(
+                NaiveDateTime::parse_from_str(&row.1, TIMESTAMP_FORMAT)
+                    .expect("A scan timestamp from the history database."),
+            );
+            value.push(row.2);
+            listed_price.push(row.3);
+            retail_price.push(row.4);
+            currency.push(row.5);
+            region.push(row.6);
+        }
+
+        DataFrame::new(vec![
+            Series::new("scan_id", scan_id),
+            Series::new("scanned_at", scanned_at),
+            Series::new("value", value),
+            Series::new("listed_price", listed_price),
+            Series::new("retail_price", retail_price),
+            Series::new("currency", currency),
+            Series::new("region", region),
+        ])
+        .expect("A Polars DataFrame of a set's history.")
+    }
+
+    /// each set's latest record beside the earlier record picked by `since`
+    ///
+    /// records are only compared within the same region and currency, so a UK
+    /// scan after a US one isn't a change in price
+    pub fn changes(&self, since: Since) -> DataFrame {
+        // anything before the start of the next day is on or before the date
+        let cutoff = match since {
+            Since::Last => None,
+            Since::Date(date) => Some(
+                date.succ_opt()
+                    .expect("The day after the --since date.")
+                    .and_hms_opt(0, 0, 0)
+                    .expect("Midnight as a time.")
+                    .format(TIMESTAMP_FORMAT)
+                    .to_string(),
+            ),
+        };
+
+        let mut statement = self
+            .connection
+            .prepare(
+                "WITH ranked AS (
+                    SELECT records.set_number, records.region, records.currency, records.name,
+                        scans.id AS scan_id, scans.scanned_at, records.value, records.listed_price,
+                        ROW_NUMBER() OVER (
+                            PARTITION BY records.set_number, records.region, records.currency
+                            ORDER BY scans.id DESC
+                        ) AS recency
+                    FROM records JOIN scans ON scans.id = records.scan_id
+                ),
+                latest AS (SELECT * FROM ranked WHERE recency = 1),
+                earlier AS (
+                    SELECT *, ROW_NUMBER() OVER (
+                        PARTITION BY set_number, region, currency ORDER BY scan_id DESC
+                    ) AS earlier_recency
+                    FROM ranked
+                    WHERE recency > 1 AND (?1 IS NULL OR scanned_at < ?1)
+                )
+                SELECT latest.set_number, latest.region, latest.currency, latest.name,
+                    earlier.scanned_at, earlier.value, earlier.listed_price,
+                    latest.scanned_at, latest.value, latest.listed_price
+                FROM latest JOIN earlier
+                    ON earlier.set_number = latest.set_number
+                    AND earlier.region IS latest.region
+                    AND earlier.currency IS latest.currency
+                    AND earlier.earlier_recency = 1
+                ORDER BY latest.set_number, latest.region, latest.currency",
+            )
+            .expect("A prepared query of changes between scans.");
+
+        let mut set_number: Vec<String> = vec![];
+        let mut region: Vec<Option<String>> = vec![];
+        let mut currency: Vec<Option<String>> = vec![];
+        let mut name: Vec<Option<String>> = vec![];
+        let mut previous_scanned_at: Vec<NaiveDateTime> = vec![];
+        let mut previous_value: Vec<Option<f64>> = vec![];
+        let mut previous_listed_price: Vec<Option<f64>> = vec![];
+        let mut scanned_at: Vec<NaiveDateTime> = vec![];
+        let mut value: Vec<Option<f64>> = vec![];
+        let mut listed_price: Vec<Option<f64>> = vec![];
+
+        let timestamp = |timestamp: String| {
+            NaiveDateTime::parse_from_str(&timestamp, TIMESTAMP_FORMAT)
+                .expect("A scan timestamp from the history database.")
+        };
+        let mut rows = statement
+            .query(params![cutoff])
+            .expect("The rows of changes between scans.");
+        while let Some(row) = rows.next().expect("A row of changes between scans.") {
+            let column = "A column of changes between scans.";
+            set_number.push(row.get(0).expect(column));
+            region.push(row.get(1).expect(column));
+            currency.push(row.get(2).expect(column));
+            name.push(row.get(3).expect(column));
+            previous_scanned_at.push(timestamp(row.get(4).expect(column)));
+            previous_value.push(row.get(5).expect(column));
+            previous_listed_price.push(row.get(6).expect(column));
+            scanned_at.push(timestamp(row.get(7).expect(column)));
+            value.push(row.get(8).expect(column));
+            listed_price.push(row.get(9).expect(column));
+        }
+
+        DataFrame::new(vec![
+            Series::new("set_number", set_number),
+            Series::new("region", region),
+            Series::new("currency", currency),
+            Series::new("name", name),
+            Series::new("previous_scanned_at", previous_scanned_at),
+            Series::new("previous_value", previous_value),
+            Series::new("previous_listed_price", previous_listed_price),
+            Series::new("scanned_at", scanned_at),
+            Series::new("value", value),
+            Series::new("listed_price", listed_price),
+        ])
+        .expect("A Polars DataFrame of changes between scans.")
+    }
+}
@@ -0,0 +1,78 @@
+//! Returns on a set since it was released at retail
+//!
+//! Real returns are deflated with the US CPI-U annual averages in cpi_u.csv,
+//! which is bundled into the binary. Sets released after the table's last
+//! year use that year's CPI, as does today.
+
+use polars::prelude::*;
+use std::io::Cursor;
+
+// BLS CPI-U, U.S. city average, all items, annual average (1982-84 = 100)
+const CPI_U: &str = include_str!("cpi_u.csv");
+
+fn read_cpi() -> DataFrame {
+    let mut schema = Schema::new();
+    schema.with_column("year".to_string(), DataType::Int32);
+    schema.with_column("cpi".to_string(), DataType::Float64);
+
+    CsvReader::new(Cursor::new(CPI_U))
+        .with_dtypes(Some(&schema))
+        .has_header(true)
+        .finish()
+        .expect("A polars DataFrame from the bundled CPI table.")
+}
+
+/// roi_since_retail, cagr and real_roi_since_retail as of `as_of_year`
+///
+/// the release year stands in for the release date so a set released this
+/// year has no cagr yet
+pub fn investment_metrics(lf: LazyFrame, as_of_year: i32) -> LazyFrame {
+    let cpi = read_cpi();
+    let years = cpi
+        .column("year")
+        .expect("The years of the CPI table.")
+        .i32()
+        .expect("CPI years as i32.");
+    let latest_year = years.max().expect("The latest year of the CPI table.");
+    let cpi_in = |year: i32| -> f64 {
+        let row = years
+            .into_iter()
+            .position(|cpi_year| cpi_year == Some(year.min(latest_year)))
+            .unwrap_or_else(|| panic!("{year} in the CPI table."));
+        cpi.column("cpi")
+            .expect("The cpi column.")
+            .f64()
+            .expect("cpi as f64.")
+            .get(row)
+            .expect("A CPI for every year in the table.")
+    };
+    let latest_cpi = cpi_in(latest_year);
+    let cpi_now = cpi_in(as_of_year);
+
+    let growth = col("value") / col("retail_price");
+    let years_since_release = (lit(as_of_year) - col("year")).cast(DataType::Float64);
+
+    lf.left_join(
+        cpi.clone()
+            .lazy()
+            .select([col("year"), col("cpi").alias("release_cpi")]),
+        col("year"),
+        col("year"),
+    )
+    .with_columns([
+        (growth.clone() - lit(1.0)).alias("roi_since_retail"),
+        when(years_since_release.clone().gt(lit(0.0)))
+            .then(growth.clone().pow(lit(1.0) / years_since_release) - lit(1.0))
+            .otherwise(lit(NULL).cast(DataType::Float64))
+            .alias("cagr"),
+        // what retail would cost in today's money
+        (growth
+            / (lit(cpi_now)
+                / when(col("year").gt(lit(latest_year)))
+                    .then(lit(latest_cpi))
+                    .otherwise(col("release_cpi")))
+            - lit(1.0))
+        .alias("real_roi_since_retail"),
+    ])
+    .drop_columns(["release_cpi"])
+}
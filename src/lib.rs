@@ -1,9 +1,35 @@
 //! Contains help text for app, config, etc.
 //! as well as other modules.
 
+mod benchmarks;
+mod budget;
+mod changes;
 mod command;
+mod costs;
+mod daemon;
+mod exchange;
+mod expr;
+mod history;
+mod investment;
+mod lookup;
+mod lot;
+mod money;
+mod notify;
+mod outliers;
+mod portfolio;
+mod price_history;
+mod region;
+mod results;
+mod scoring;
+mod scrape;
 mod scraper_utils;
 mod set_data;
+mod set_list;
+mod shipping;
+mod sql;
+mod stats;
+mod text;
+mod watchlist;
 
 #[doc(hidden)]
 pub use command::Leget;
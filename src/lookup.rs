@@ -0,0 +1,30 @@
+//! Finding sets in the set list from a scanned barcode
+
+use crate::set_list::read_set_list;
+
+use polars::prelude::*;
+
+pub fn lookup(barcode: &str) -> color_eyre::Result<()> {
+    let digits: String = barcode.chars().filter(char::is_ascii_digit).collect();
+
+    // a 12 digit UPC-A is the same code as a 13 digit EAN with a leading 0
+    let (upc, ean) = match digits.len() {
+        12 => (digits.clone(), format!("0{digits}")),
+        13 if digits.starts_with('0') => (digits[1..].to_string(), digits.clone()),
+        _ => (digits.clone(), digits.clone()),
+    };
+
+    let df = read_set_list()
+        .lazy()
+        .filter(col("upc").eq(lit(upc)).or(col("ean").eq(lit(ean))))
+        .collect()
+        .expect("The set list filtered by barcode.");
+
+    if df.height() == 0 {
+        println!("No set in the set list has the barcode {barcode}. You might need to use --update-set-list.");
+    } else {
+        println!("{}", &df);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,141 @@
+//! Evaluating a lot of sets offered at one asking price
+//!
+//! The lot is a csv with columns `set_number,condition,quantity` where the
+//! condition is `new` or `used` and a missing quantity counts as one.
+
+use crate::money::Currency;
+use crate::portfolio::{conditions, current_value};
+use crate::set_list::with_variant;
+
+use polars::prelude::*;
+
+pub fn read_lot(path: &str) -> DataFrame {
+    let mut schema = Schema::new();
+    schema.with_column("set_number".to_string(), DataType::Utf8);
+    schema.with_column("condition".to_string(), DataType::Utf8);
+    schema.with_column("quantity".to_string(), DataType::UInt32);
+
+    let mut df = CsvReader::from_path(path)
+        .expect("A reader connection to the lot.")
+        .with_dtypes(Some(&schema))
+        .has_header(true)
+        .finish()
+        .expect("A polars DataFrame from the lot.");
+
+    // a plain list of set numbers is a lot too
+    if df.column("condition").is_err() {
+        df.with_column(Series::full_null("condition", df.height(), &DataType::Utf8))
+            .expect("A null condition column added to the lot.");
+    }
+    if df.column("quantity").is_err() {
+        df.with_column(Series::full_null(
+            "quantity",
+            df.height(),
+            &DataType::UInt32,
+        ))
+        .expect("A null quantity column added to the lot.");
+    }
+
+    let set_numbers: Vec<String> = df
+        .column("set_number")
+        .expect("The set numbers of the lot.")
+        .utf8()
+        .expect("Set numbers as utf8.")
+        .into_no_null_iter()
+        .map(with_variant)
+        .collect();
+    df.with_column(Series::new("set_number", set_numbers))
+        .expect("The lot with full set numbers.");
+    df.with_column(conditions(&df))
+        .expect("The lot with lowercase conditions.");
+
+    df
+}
+
+/// an asking price, which has to be more than nothing to compare the lot to
+pub fn parse_ask(ask: &str) -> Result<f64, String> {
+    match ask.parse::<f64>() {
+        Ok(ask) if ask > 0.0 => Ok(ask),
+        Ok(_) => Err("the asking price should be more than 0".to_string()),
+        Err(_) => Err(format!("{ask} isn't a number")),
+    }
+}
+
+/// print each set's share of the lot's value and how the lot compares to `ask`
+///
+/// the sets that carry the lot are the most valuable ones that, together,
+/// are needed to cover the asking price. values and `ask` are in `currency`
+pub fn lot_report(
+    lot: DataFrame,
+    sets: LazyFrame,
+    ask: f64,
+    currency: Currency,
+) -> color_eyre::Result<()> {
+    let df = lot
+        .lazy()
+        .left_join(
+            sets.select([
+                col("set_number"),
+                col("name"),
+                col("value"),
+                col("value_used"),
+            ]),
+            col("set_number"),
+            col("set_number"),
+        )
+        .with_columns([col("quantity").fill_null(lit(1u32)), current_value()])
+        .with_column((col("current_value") * col("quantity")).alias("line_value"))
+        .sort(
+            "line_value",
+            SortOptions {
+                descending: true,
+                nulls_last: true,
+            },
+        )
+        .with_columns([
+            (col("line_value") / col("line_value").sum() * lit(100.0)).alias("share_of_value"),
+            // what the more valuable sets already cover before this one
+            (col("line_value").cumsum(false) - col("line_value"))
+                .lt(lit(ask))
+                .and(col("line_value").is_not_null())
+                .alias("carries_lot"),
+        ])
+        .select([
+            col("set_number"),
+            col("name"),
+            col("condition"),
+            col("quantity"),
+            col("current_value"),
+            col("line_value"),
+            col("share_of_value"),
+            col("carries_lot"),
+            lit(currency.code()).alias("currency"),
+        ])
+        .collect()
+        .expect("The lot's sets by their share of its value.");
+
+    let total_value = df
+        .column("line_value")
+        .expect("The line values of the lot.")
+        .sum::<f64>()
+        .unwrap_or_default();
+    let summary = df![
+        "total_value" => [total_value],
+        "ask" => [ask],
+        "value_to_ask" => [total_value / ask],
+    ]
+    .expect("The lot's summary.");
+
+    println!("{}", &df);
+    println!("{}", &summary);
+
+    let unvalued = df
+        .column("line_value")
+        .expect("The line values of the lot.")
+        .null_count();
+    if unvalued > 0 {
+        println!("warning: {unvalued} sets in the lot have no value and count as nothing.");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,331 @@
+//! Parsing scraped prices into exact amounts
+//!
+//! Prices are parsed into integer cents so that thousands separators and
+//! missing cents are read exactly. They only become floating point units once
+//! they're put in a DataFrame.
+
+use clap::ValueEnum;
+use lazy_static::lazy_static;
+use regex::Regex;
+
+lazy_static! {
+    // longer symbols go first so "C$" isn't read as "$". Plain spaces aren't
+    // thousands separators or "$79 2023" would run into the year, see
+    // is_cut_short() for numbers grouped by them
+    static ref RE_MONEY: Regex = Regex::new(
+        r"(?P<prefix>CA\$|C\$|US\$|CAD|USD|GBP|EUR|\$|£|€)\s?(?P<number>\d+(?:[.,'\x{A0}\x{2009}\x{202F}]\d{3})*(?:[.,]\d{1,2})?)|(?P<amount>\d+(?:[.,'\x{A0}\x{2009}\x{202F}]\d{3})*(?:[.,]\d{1,2})?)\s?(?P<suffix>CAD|USD|GBP|EUR|£|€)"
+    )
+    .expect("A Regex of an amount with a currency symbol or code.");
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, ValueEnum)]
+pub enum Currency {
+    Usd,
+    Gbp,
+    Eur,
+    Cad,
+}
+
+impl Currency {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Currency::Usd => "USD",
+            Currency::Gbp => "GBP",
+            Currency::Eur => "EUR",
+            Currency::Cad => "CAD",
+        }
+    }
+
+    fn from_symbol(symbol: &str) -> Option<Self> {
+        match symbol {
+            "$" | "US$" | "USD" => Some(Currency::Usd),
+            "£" | "GBP" => Some(Currency::Gbp),
+            "€" | "EUR" => Some(Currency::Eur),
+            "C$" | "CA$" | "CAD" => Some(Currency::Cad),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Money {
+    pub cents: i64,
+    pub currency: Currency,
+}
+
+/// the first amount in `text` in the given currency
+pub fn parse_money_in(text: &str, currency: Currency) -> Option<Money> {
+    parse_all(text).find(|money| money.currency == currency)
+}
+
+fn parse_all(text: &str) -> impl Iterator<Item = Money> + '_ {
+    RE_MONEY.captures_iter(text).filter_map(|captures| {
+        let (symbol, number) = match (captures.name("prefix"), captures.name("number")) {
+            (Some(symbol), Some(number)) => (symbol, number),
+            _ => (captures.name("suffix")?, captures.name("amount")?),
+        };
+        if is_cut_short(text, number.start(), number.end()) {
+            return None;
+        }
+        Some(Money {
+            cents: parse_cents(number.as_str())?,
+            currency: Currency::from_symbol(symbol.as_str())?,
+        })
+    })
+}
+
+// whether the number at `start..end` is only part of a longer one, e.g. the
+// "299,99" of "1 299,99 €" or the "1" of "$ 1 299", which the pattern can't
+// tell apart on its own
+fn is_cut_short(text: &str, start: usize, end: usize) -> bool {
+    let is_separator = |c: char| matches!(c, '.' | ',' | '\'') || c.is_whitespace();
+
+    let mut before = text[..start].chars().rev();
+    let runs_on_before = match (before.next(), before.next()) {
+        (Some(c), _) if c.is_ascii_digit() => true,
+        (Some(c), Some(digit)) => is_separator(c) && digit.is_ascii_digit(),
+        _ => false,
+    };
+
+    let number = &text[start..end];
+    let mut after = text[end..].chars();
+    let runs_on_after = match after.next() {
+        Some(c) if c.is_ascii_digit() => true,
+        Some('.' | ',' | '\'') => after.next().is_some_and(|c| c.is_ascii_digit()),
+        // a whole number of up to three digits then a space and three more
+        // digits is grouped by spaces, unlike "$79 2023" or "$79.99 123"
+        Some(c) if c.is_whitespace() => {
+            number.len() <= 3
+                && number.chars().all(|c| c.is_ascii_digit())
+                && after.take_while(|c| c.is_ascii_digit()).count() == 3
+        }
+        _ => false,
+    };
+
+    runs_on_before || runs_on_after
+}
+
+/// parse a bare number like "1,299.99", "1.299,99", "12,99" or "79" into cents
+///
+/// the last separator is a decimal point when it has one or two digits after it,
+/// otherwise every separator is a thousands separator. Anything that isn't
+/// grouped in threes is None, as is a lone "." before three digits since
+/// "54.995" and "1.299" can't be told apart.
+pub fn parse_cents(number: &str) -> Option<i64> {
+    let (units, fraction) = match number.rfind(['.', ',']) {
+        Some(index) if (2..=3).contains(&(number.len() - index)) => {
+            (&number[..index], &number[index + 1..])
+        }
+        _ => (number, ""),
+    };
+    if !fraction.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let separators: Vec<char> = units.chars().filter(|c| !c.is_ascii_digit()).collect();
+    let groups: Vec<&str> = units.split(|c: char| !c.is_ascii_digit()).collect();
+    if let Some(&separator) = separators.first() {
+        let is_separator = |c: char| matches!(c, '.' | ',' | '\'') || c.is_whitespace();
+        let decimal_point = number[units.len()..].chars().next();
+        if !is_separator(separator)
+            || separators.iter().any(|c| *c != separator)
+            || decimal_point == Some(separator)
+            || !(1..=3).contains(&groups[0].len())
+            || groups[0].starts_with('0')
+            || groups[1..].iter().any(|group| group.len() != 3)
+            || (fraction.is_empty() && separators == ['.'])
+        {
+            return None;
+        }
+    }
+
+    let units = groups.concat();
+    if units.is_empty() && fraction.is_empty() {
+        return None;
+    }
+    let units: i64 = if units.is_empty() {
+        0
+    } else {
+        units.parse().ok()?
+    };
+    let fraction: i64 = match fraction.len() {
+        0 => 0,
+        1 => fraction.parse::<i64>().ok()? * 10,
+        _ => fraction.parse().ok()?,
+    };
+
+    units.checked_mul(100)?.checked_add(fraction)
+}
+
+/// polars columns are kept in whole currency units
+pub fn cents_to_units(cents: &[Option<i64>]) -> Vec<Option<f64>> {
+    cents
+        .iter()
+        .map(|cents| cents.map(|cents| cents as f64 / 100.0))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usd(cents: i64) -> Option<Money> {
+        Some(Money {
+            cents,
+            currency: Currency::Usd,
+        })
+    }
+
+    #[test]
+    fn symbols_and_codes() {
+        assert_eq!(parse_money_in("$79.99", Currency::Usd), usd(7999));
+        assert_eq!(parse_money_in("US$79.99", Currency::Usd), usd(7999));
+        assert_eq!(parse_money_in("79.99 USD", Currency::Usd), usd(7999));
+        assert_eq!(
+            parse_money_in("£69.99", Currency::Gbp).map(|money| money.cents),
+            Some(6999)
+        );
+        assert_eq!(
+            parse_money_in("GBP 69.99", Currency::Gbp).map(|money| money.cents),
+            Some(6999)
+        );
+        assert_eq!(
+            parse_money_in("€79.99", Currency::Eur).map(|money| money.cents),
+            Some(7999)
+        );
+        assert_eq!(
+            parse_money_in("79,99 €", Currency::Eur).map(|money| money.cents),
+            Some(7999)
+        );
+        assert_eq!(
+            parse_money_in("79,99€", Currency::Eur).map(|money| money.cents),
+            Some(7999)
+        );
+        assert_eq!(
+            parse_money_in("C$99.99", Currency::Cad).map(|money| money.cents),
+            Some(9999)
+        );
+        assert_eq!(
+            parse_money_in("CA$99.99", Currency::Cad).map(|money| money.cents),
+            Some(9999)
+        );
+        assert_eq!(
+            parse_money_in("99.99 CAD", Currency::Cad).map(|money| money.cents),
+            Some(9999)
+        );
+    }
+
+    #[test]
+    fn canadian_dollars_are_not_us_dollars() {
+        assert_eq!(parse_money_in("C$99.99", Currency::Usd), None);
+        assert_eq!(parse_money_in("CA$99.99", Currency::Usd), None);
+        assert_eq!(parse_money_in("C$99.99 / $74.99", Currency::Usd), usd(7499));
+    }
+
+    #[test]
+    fn first_amount_in_the_currency() {
+        assert_eq!(
+            parse_money_in("£69.99 (about $89.99)", Currency::Usd),
+            usd(8999)
+        );
+        assert_eq!(parse_money_in("$10.00 - $20.00", Currency::Usd), usd(1000));
+    }
+
+    #[test]
+    fn thousands_separators() {
+        assert_eq!(parse_money_in("$1,299.99", Currency::Usd), usd(129999));
+        assert_eq!(parse_money_in("$1,299", Currency::Usd), usd(129900));
+        assert_eq!(
+            parse_money_in("$1,234,567.89", Currency::Usd),
+            usd(123456789)
+        );
+        assert_eq!(
+            parse_money_in("1.299,99 €", Currency::Eur).map(|money| money.cents),
+            Some(129999)
+        );
+        assert_eq!(
+            parse_money_in("1\u{A0}299,99 €", Currency::Eur).map(|money| money.cents),
+            Some(129999)
+        );
+        assert_eq!(
+            parse_money_in("1\u{202F}299,99 €", Currency::Eur).map(|money| money.cents),
+            Some(129999)
+        );
+        assert_eq!(parse_cents("1'299.99"), Some(129999));
+        assert_eq!(parse_cents("1.299.000"), Some(129900000));
+    }
+
+    #[test]
+    fn missing_cents() {
+        assert_eq!(parse_money_in("$79", Currency::Usd), usd(7900));
+        assert_eq!(parse_money_in("$79.9", Currency::Usd), usd(7990));
+        assert_eq!(parse_cents("0"), Some(0));
+    }
+
+    #[test]
+    fn decimal_commas() {
+        assert_eq!(parse_cents("12,99"), Some(1299));
+        assert_eq!(parse_cents("12,9"), Some(1290));
+        assert_eq!(parse_cents(",99"), Some(99));
+    }
+
+    #[test]
+    fn spaces_are_not_thousands_separators() {
+        assert_eq!(parse_money_in("$79 2023", Currency::Usd), usd(7900));
+        assert_eq!(parse_money_in("$79.99 123", Currency::Usd), usd(7999));
+        assert_eq!(parse_money_in("$ 79.99", Currency::Usd), usd(7999));
+        // rather than the end of the number
+        assert_eq!(parse_money_in("1 299,99 €", Currency::Eur), None);
+        assert_eq!(parse_money_in("$ 1 299", Currency::Usd), None);
+        assert_eq!(parse_money_in("$1 299.99", Currency::Usd), None);
+        assert_eq!(
+            parse_money_in("Set 10300 for 99,99 €", Currency::Eur).map(|money| money.cents),
+            Some(9999)
+        );
+    }
+
+    #[test]
+    fn apostrophe_separators() {
+        assert_eq!(parse_money_in("1'299.99 USD", Currency::Usd), usd(129999));
+        assert_eq!(parse_money_in("$1'299", Currency::Usd), usd(129900));
+    }
+
+    #[test]
+    fn numbers_cut_short() {
+        assert_eq!(parse_money_in("$1,29,999", Currency::Usd), None);
+        assert_eq!(parse_money_in("12345,678 €", Currency::Eur), None);
+    }
+
+    #[test]
+    fn ambiguous_fractions() {
+        assert_eq!(parse_cents("0.125"), None);
+        assert_eq!(parse_cents("0,125"), None);
+        assert_eq!(parse_cents("54.995"), None);
+        assert_eq!(parse_cents("1.299"), None);
+        assert_eq!(parse_money_in("$54.995", Currency::Usd), None);
+    }
+
+    #[test]
+    fn rejected() {
+        assert_eq!(parse_cents(""), None);
+        assert_eq!(parse_cents("abc"), None);
+        assert_eq!(parse_cents("79."), None);
+        assert_eq!(parse_cents("1,29,999"), None);
+        assert_eq!(parse_cents("1234,567"), None);
+        assert_eq!(parse_cents("1,299,99"), None);
+        assert_eq!(parse_cents("1,299.999.99"), None);
+        assert_eq!(parse_cents("12.5x"), None);
+        assert_eq!(parse_cents("99999999999999999999"), None);
+        assert_eq!(parse_money_in("79.99", Currency::Usd), None);
+        assert_eq!(parse_money_in("$", Currency::Usd), None);
+        assert_eq!(parse_money_in("£69.99", Currency::Usd), None);
+    }
+
+    #[test]
+    fn cents_become_units() {
+        assert_eq!(
+            cents_to_units(&[Some(129999), None, Some(5)]),
+            vec![Some(1299.99), None, Some(0.05)]
+        );
+    }
+}
@@ -0,0 +1,237 @@
+//! Posting deals to a webhook
+//!
+//! Each deal is sent once per set and listed price; what has been sent is kept
+//! in the leget.db history so reruns and scheduled scans don't repeat it.
+
+use crate::history::{History, HISTORY_DB_PATH};
+
+use polars::prelude::*;
+use serde::Serialize;
+use std::time::Duration;
+
+// doubled after every failed attempt
+const FIRST_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+#[derive(Serialize)]
+struct Deal<'a> {
+    set_number: &'a str,
+    name: Option<&'a str>,
+    price: f64,
+    value: f64,
+    // percent below value
+    discount: f64,
+    url: String,
+}
+
+pub struct Notifier {
+    client: reqwest::Client,
+    url: String,
+    retries: u32,
+    history_path: String,
+    first_retry_delay: Duration,
+}
+
+impl Notifier {
+    pub fn new(url: &str, retries: u32) -> Self {
+        Notifier {
+            client: reqwest::Client::new(),
+            url: url.to_string(),
+            retries,
+            history_path: HISTORY_DB_PATH.to_string(),
+            first_retry_delay: FIRST_RETRY_DELAY,
+        }
+    }
+
+    /// post every row of a DataFrame with set_number, name, listed_price and value
+    /// that hasn't been posted before
+    pub async fn notify(&self, deals: &DataFrame) {
+        let mut history = History::open(&self.history_path);
+
+        let set_numbers = deals
+            .column("set_number")
+            .expect("The set numbers of the deals.")
+            .utf8()
+            .expect("Set numbers as utf8.");
+        let names = deals
+            .column("name")
+            .expect("The names of the deals.")
+            .utf8()
+            .expect("Names as utf8.");
+        let listed_prices = deals
+            .column("listed_price")
+            .expect("The listed prices of the deals.")
+            .f64()
+            .expect("Listed prices as f64.");
+        let values = deals
+            .column("value")
+            .expect("The values of the deals.")
+            .f64()
+            .expect("Values as f64.");
+
+        for row in 0..deals.height() {
+            let (Some(set_number), Some(price), Some(value)) = (
+                set_numbers.get(row),
+                listed_prices.get(row),
+                values.get(row),
+            ) else {
+                continue;
+            };
+            if history.was_notified(set_number, price) {
+                continue;
+            }
+
+            let deal = Deal {
+                set_number,
+                name: names.get(row),
+                price,
+                value,
+                discount: (value - price) / value * 100.0,
+                url: format!("https://www.brickeconomy.com/set/{}/", set_number),
+            };
+            if self.post(&deal).await {
+                history.record_notification(set_number, price);
+            }
+        }
+    }
+
+    // false if every attempt failed
+    async fn post(&self, deal: &Deal<'_>) -> bool {
+        let mut delay = self.first_retry_delay;
+        for attempt in 0..=self.retries {
+            if attempt > 0 {
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+            }
+            match self.client.post(&self.url).json(deal).send().await {
+                Ok(response) if response.status().is_success() => return true,
+                Ok(response) => println!(
+                    "warning: the webhook answered {} for set #{}.",
+                    response.status(),
+                    deal.set_number
+                ),
+                Err(error) => println!(
+                    "warning: the webhook couldn't be reached for set #{}: {error}",
+                    deal.set_number
+                ),
+            }
+        }
+        println!(
+            "warning: gave up posting set #{} to the webhook after {} attempts.",
+            deal.set_number,
+            self.retries + 1
+        );
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    // a webhook that fails its first `failures` posts then accepts the rest,
+    // returning its url and how many posts it has had
+    async fn webhook(failures: usize) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let posts = Arc::new(AtomicUsize::new(0));
+
+        let counter = posts.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = vec![];
+                let mut buffer = [0; 1024];
+                // read up to the end of the body
+                loop {
+                    let read = stream.read(&mut buffer).await.unwrap();
+                    request.extend_from_slice(&buffer[..read]);
+                    let text = String::from_utf8_lossy(&request);
+                    if let Some(end) = text.find("\r\n\r\n") {
+                        let length = text
+                            .lines()
+                            .find_map(|line| {
+                                line.to_lowercase()
+                                    .strip_prefix("content-length:")
+                                    .map(|length| length.trim().parse::<usize>().unwrap())
+                            })
+                            .unwrap_or(0);
+                        if request.len() >= end + 4 + length || read == 0 {
+                            break;
+                        }
+                    }
+                }
+
+                let status = if counter.fetch_add(1, Ordering::SeqCst) < failures {
+                    "500 Internal Server Error"
+                } else {
+                    "200 OK"
+                };
+                let response =
+                    format!("HTTP/1.1 {status}\r\ncontent-length: 0\r\nconnection: close\r\n\r\n");
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+
+        (url, posts)
+    }
+
+    fn notifier(url: &str, retries: u32, name: &str) -> Notifier {
+        let history_path = std::env::temp_dir()
+            .join(format!("leget-{name}-{}.db", std::process::id()))
+            .to_string_lossy()
+            .into_owned();
+        let _ = std::fs::remove_file(&history_path);
+
+        Notifier {
+            client: reqwest::Client::new(),
+            url: url.to_string(),
+            retries,
+            history_path,
+            first_retry_delay: Duration::from_millis(1),
+        }
+    }
+
+    fn deals() -> DataFrame {
+        df!(
+            "set_number" => ["10300-1"],
+            "name" => ["Back to the Future Time Machine"],
+            "listed_price" => [120.0],
+            "value" => [200.0]
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn retries_then_remembers_the_deal() {
+        let (url, posts) = webhook(2).await;
+        let notifier = notifier(&url, 3, "retries");
+
+        notifier.notify(&deals()).await;
+        assert_eq!(posts.load(Ordering::SeqCst), 3);
+
+        // already sent at this price
+        notifier.notify(&deals()).await;
+        assert_eq!(posts.load(Ordering::SeqCst), 3);
+
+        std::fs::remove_file(&notifier.history_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn gives_up_without_remembering_the_deal() {
+        let (url, posts) = webhook(usize::MAX).await;
+        let notifier = notifier(&url, 1, "gives-up");
+
+        notifier.notify(&deals()).await;
+        assert_eq!(posts.load(Ordering::SeqCst), 2);
+
+        // nothing was sent so the next run tries again
+        notifier.notify(&deals()).await;
+        assert_eq!(posts.load(Ordering::SeqCst), 4);
+
+        std::fs::remove_file(&notifier.history_path).unwrap();
+    }
+}
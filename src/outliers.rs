@@ -0,0 +1,84 @@
+//! Flagging listings priced unlike the sets around them
+//!
+//! A listing far below its peers' listed_price / value is often a used or
+//! incomplete set, or bait. Peers are the sets in the same theme and piece
+//! band, and how far is the robust z-score
+//!
+//! ```text
+//! 0.6745 * (ratio - median(ratio)) / median(|ratio - median(ratio)|)
+//! ```
+//!
+//! which, unlike a z-score from the mean, isn't thrown off by the outliers
+//! themselves.
+
+use polars::prelude::*;
+
+// fewer peers than this and there's nothing to compare to
+const MIN_PEERS: u32 = 3;
+
+// sets with a similar number of pieces, e.g. a 2,000 piece set isn't
+// priced like a 200 piece one
+fn piece_band() -> Expr {
+    when(col("pieces").lt(lit(100u32)))
+        .then(lit("<100"))
+        .when(col("pieces").lt(lit(250u32)))
+        .then(lit("100-249"))
+        .when(col("pieces").lt(lit(500u32)))
+        .then(lit("250-499"))
+        .when(col("pieces").lt(lit(1000u32)))
+        .then(lit("500-999"))
+        .when(col("pieces").lt(lit(2500u32)))
+        .then(lit("1000-2499"))
+        .otherwise(lit("2500+"))
+        .alias("piece_band")
+}
+
+/// add outlier_z and an outlier flag for |outlier_z| over `threshold`
+pub fn flag_outliers(lf: LazyFrame, threshold: f64) -> LazyFrame {
+    let peers = [col("theme"), col("piece_band")];
+
+    // windows can't nest so each median gets its own column
+    lf.with_column(piece_band())
+        .with_column((col("listed_price") / col("value")).alias("price_to_value"))
+        .with_columns([
+            col("price_to_value")
+                .median()
+                .over(peers.clone())
+                .alias("peer_median"),
+            col("set_number")
+                .count()
+                .over(peers.clone())
+                .alias("peer_count"),
+        ])
+        .with_column(
+            (col("price_to_value") - col("peer_median"))
+                .abs()
+                .alias("peer_deviation"),
+        )
+        .with_column(col("peer_deviation").median().over(peers).alias("peer_mad"))
+        .with_column(
+            when(
+                col("peer_count")
+                    .gt_eq(lit(MIN_PEERS))
+                    .and(col("peer_mad").gt(lit(0.0))),
+            )
+            .then(lit(0.6745) * (col("price_to_value") - col("peer_median")) / col("peer_mad"))
+            .otherwise(lit(NULL).cast(DataType::Float64))
+            .alias("outlier_z"),
+        )
+        .with_column(
+            col("outlier_z")
+                .abs()
+                .gt(lit(threshold))
+                .fill_null(false)
+                .alias("outlier"),
+        )
+        .drop_columns([
+            "piece_band",
+            "price_to_value",
+            "peer_median",
+            "peer_count",
+            "peer_deviation",
+            "peer_mad",
+        ])
+}
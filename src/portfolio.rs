@@ -0,0 +1,214 @@
+//! Tracking the value of sets we own
+//!
+//! The portfolio is a csv with columns
+//! `set_number,condition,purchase_price,purchase_date,quantity` where the
+//! condition is `new` or `used`, the purchase price is per set in --currency
+//! (USD without it, since values are in USD) and the purchase date is a
+//! YYYY-MM-DD date.
+
+use crate::money::Currency;
+use crate::set_list::with_variant;
+
+use chrono::{Local, NaiveDate};
+use polars::prelude::*;
+
+pub const PORTFOLIO_PATH: &str = "portfolio.csv";
+
+// holdings newer than this have no annualized_return
+const MIN_YEARS_TO_ANNUALIZE: f64 = 1.0;
+
+pub fn read_portfolio(path: &str) -> DataFrame {
+    let mut schema = Schema::new();
+    schema.with_column("set_number".to_string(), DataType::Utf8);
+    schema.with_column("condition".to_string(), DataType::Utf8);
+    schema.with_column("purchase_price".to_string(), DataType::Float64);
+    schema.with_column("purchase_date".to_string(), DataType::Utf8);
+    schema.with_column("quantity".to_string(), DataType::UInt32);
+
+    let mut df = CsvReader::from_path(path)
+        .expect("A reader connection to the portfolio.")
+        .with_dtypes(Some(&schema))
+        .has_header(true)
+        .finish()
+        .expect("A polars DataFrame from the portfolio.");
+
+    let utf8 = |name: &str| {
+        df.column(name)
+            .unwrap_or_else(|_| panic!("The {name} column of the portfolio."))
+            .utf8()
+            .unwrap_or_else(|_| panic!("{name} as utf8."))
+            .clone()
+    };
+    let set_numbers: Vec<String> = utf8("set_number")
+        .into_no_null_iter()
+        .map(with_variant)
+        .collect();
+    let purchase_dates: Vec<NaiveDate> = utf8("purchase_date")
+        .into_no_null_iter()
+        .map(|date| {
+            NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d")
+                .unwrap_or_else(|_| panic!("{date} as a YYYY-MM-DD date."))
+        })
+        .collect();
+    let today = Local::now().date_naive();
+    let years_held: Vec<f64> = purchase_dates
+        .iter()
+        .map(|date| (today - *date).num_days() as f64 / 365.25)
+        .collect();
+
+    df.with_column(Series::new("set_number", set_numbers))
+        .expect("The portfolio with full set numbers.");
+    df.with_column(conditions(&df))
+        .expect("The portfolio with lowercase conditions.");
+    df.with_column(Series::new("purchase_date", purchase_dates))
+        .expect("The portfolio with purchase dates as dates.");
+    df.with_column(Series::new("years_held", years_held))
+        .expect("The portfolio with years held.");
+
+    df
+}
+
+/// a condition column of `new` or `used`, with missing conditions taken as new
+pub fn conditions(df: &DataFrame) -> Series {
+    let conditions: Vec<String> = df
+        .column("condition")
+        .expect("The condition column.")
+        .utf8()
+        .expect("Conditions as utf8.")
+        .into_iter()
+        .map(|condition| {
+            let condition = condition.unwrap_or("new").trim().to_lowercase();
+            assert!(
+                condition == "new" || condition == "used",
+                "{condition} should be new or used."
+            );
+            condition
+        })
+        .collect();
+
+    Series::new("condition", conditions)
+}
+
+/// the value of a set in its condition
+pub fn current_value() -> Expr {
+    when(col("condition").eq(lit("used")))
+        .then(col("value_used"))
+        .otherwise(col("value"))
+        .alias("current_value")
+}
+
+/// print each holding, a breakdown by theme and the totals of the portfolio
+///
+/// `sets` is a freshly scraped DataFrame of the portfolio's sets with values
+/// in `currency`. holdings without a current value are left out of the
+/// breakdown and totals.
+pub fn portfolio_report(
+    portfolio: DataFrame,
+    sets: LazyFrame,
+    currency: Currency,
+) -> color_eyre::Result<()> {
+    let holdings = portfolio
+        .lazy()
+        .left_join(
+            sets.select([
+                col("set_number"),
+                col("name"),
+                col("theme"),
+                col("value"),
+                col("value_used"),
+            ]),
+            col("set_number"),
+            col("set_number"),
+        )
+        .with_columns([col("quantity").fill_null(lit(1u32)), current_value()])
+        .with_columns([
+            (col("purchase_price") * col("quantity")).alias("cost_basis"),
+            (col("current_value") * col("quantity")).alias("market_value"),
+        ])
+        .with_columns(returns())
+        .with_column(lit(currency.code()).alias("currency"))
+        .collect()
+        .expect("The portfolio's holdings at their current value.");
+
+    let valued = holdings
+        .clone()
+        .lazy()
+        .filter(col("market_value").is_not_null());
+    let totals = [
+        col("cost_basis").sum(),
+        col("market_value").sum(),
+        // weight each holding's time held by how much went into it
+        ((col("cost_basis") * col("years_held")).sum() / col("cost_basis").sum())
+            .alias("years_held"),
+    ];
+    let by_theme = valued
+        .clone()
+        .groupby([col("theme")])
+        .agg(totals.clone())
+        .with_columns(returns())
+        .sort(
+            "market_value",
+            SortOptions {
+                descending: true,
+                nulls_last: true,
+            },
+        )
+        .collect()
+        .expect("The portfolio broken down by theme.");
+    let total = valued
+        .select(totals)
+        .with_columns(returns())
+        .collect()
+        .expect("The portfolio's totals.");
+
+    println!(
+        "{}",
+        &holdings
+            .select([
+                "set_number",
+                "name",
+                "theme",
+                "condition",
+                "quantity",
+                "purchase_date",
+                "purchase_price",
+                "current_value",
+                "cost_basis",
+                "market_value",
+                "unrealized_gain",
+                "gain_percent",
+                "annualized_return",
+                "currency",
+            ])
+            .expect("The columns of the holdings report.")
+    );
+    println!("{}", &by_theme);
+    println!("{}", &total);
+
+    let unvalued = holdings
+        .column("market_value")
+        .expect("The market values of the holdings.")
+        .null_count();
+    if unvalued > 0 {
+        println!(
+            "warning: {unvalued} holdings have no current value and are left out of the totals."
+        );
+    }
+
+    Ok(())
+}
+
+// gain and return columns from cost_basis, market_value and years_held
+fn returns() -> [Expr; 3] {
+    let years_held = col("years_held");
+    let growth = col("market_value") / col("cost_basis");
+    [
+        (col("market_value") - col("cost_basis")).alias("unrealized_gain"),
+        ((growth.clone() - lit(1.0)) * lit(100.0)).alias("gain_percent"),
+        // compounding a few weeks' gain over a year makes it meaningless
+        when(years_held.clone().gt_eq(lit(MIN_YEARS_TO_ANNUALIZE)))
+            .then((growth.pow(lit(1.0) / years_held) - lit(1.0)) * lit(100.0))
+            .otherwise(lit(NULL).cast(DataType::Float64))
+            .alias("annualized_return"),
+    ]
+}
@@ -0,0 +1,152 @@
+//! Value history from the charts embedded in set pages
+//!
+//! brickeconomy draws its value charts from rows written into a script on the
+//! page, e.g. `[new Date(2021, 0, 1), 199.99, ...]`. The new value chart comes
+//! before the used one.
+
+use crate::money::cents_to_units;
+use crate::scraper_utils::make_selector;
+
+use chrono::NaiveDate;
+use lazy_static::lazy_static;
+use polars::prelude::*;
+use regex::Regex;
+use scraper::{Html, Selector};
+use std::collections::BTreeMap;
+use std::fs::File;
+
+lazy_static! {
+    static ref SCRIPT: Selector = make_selector("script");
+    static ref RE_CHART_ROW: Regex = Regex::new(
+        r"\[\s*new Date\((\d{4}),\s*(\d{1,2}),\s*(\d{1,2})\)\s*,\s*(\d+(?:\.\d+)?|null)"
+    )
+    .expect("A Regex of a chart row with a date and a value.");
+}
+
+pub const PRICE_HISTORY_PATH: &str = "price_history.csv";
+
+pub struct PriceHistory {
+    pub set_number: Vec<String>,
+    pub date: Vec<NaiveDate>,
+    // in cents of US dollars like crate::set_data::SetData
+    pub value_new: Vec<Option<i64>>,
+    pub value_used: Vec<Option<i64>>,
+}
+
+impl PriceHistory {
+    pub fn new() -> Self {
+        PriceHistory {
+            set_number: vec![],
+            date: vec![],
+            value_new: vec![],
+            value_used: vec![],
+        }
+    }
+
+    /// add every dated value charted on a set's page
+    pub fn extend_from_page(&mut self, set_number: &str, document: &Html) {
+        // date -> (new, used)
+        let mut rows: BTreeMap<NaiveDate, (Option<i64>, Option<i64>)> = BTreeMap::new();
+
+        for script in document.select(&SCRIPT) {
+            let script = script.text().collect::<String>();
+            // each chart's rows are added in their own call
+            let charts = script.split("addRows(").skip(1);
+            for (chart, chart_rows) in charts.take(2).enumerate() {
+                for row in RE_CHART_ROW.captures_iter(chart_rows) {
+                    // javascript months start at 0
+                    let date = NaiveDate::from_ymd_opt(
+                        row[1].parse().expect("A chart year as a number."),
+                        row[2].parse::<u32>().expect("A chart month as a number.") + 1,
+                        row[3].parse().expect("A chart day as a number."),
+                    );
+                    let Some(date) = date else { continue };
+                    // chart values are javascript numbers, not formatted prices
+                    let value = row[4]
+                        .parse::<f64>()
+                        .ok()
+                        .map(|value| (value * 100.0).round() as i64);
+
+                    let entry = rows.entry(date).or_insert((None, None));
+                    if chart == 0 {
+                        entry.0 = value;
+                    } else {
+                        entry.1 = value;
+                    }
+                }
+            }
+        }
+
+        for (date, (value_new, value_used)) in rows {
+            self.set_number.push(set_number.to_string());
+            self.date.push(date);
+            self.value_new.push(value_new);
+            self.value_used.push(value_used);
+        }
+    }
+
+    pub fn write(&self, path: &str) {
+        let mut df: DataFrame = DataFrame::new(vec![
+            Series::new("set_number", &self.set_number),
+            Series::new("date", &self.date),
+            Series::new("value_new", cents_to_units(&self.value_new)),
+            Series::new("value_used", cents_to_units(&self.value_used)),
+        ])
+        .expect("A Polars DataFrame of price history.");
+
+        let history_csv = File::create(path).expect("The creation of the price history csv.");
+        let mut writer: CsvWriter<File> = CsvWriter::new(history_csv).has_header(true);
+        writer
+            .finish(&mut df)
+            .expect("The writting of the price history csv.");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAGE: &str = r#"<html><body><script>
+        data.addRows([
+            [new Date(2021, 0, 1), 199.99, null],
+            [new Date(2021, 5, 15), 54.995, null],
+            [new Date(2022, 11, 31), 1299, null],
+            [new Date(2023, 1, 30), 1.5, null]
+        ]);
+        used.addRows([
+            [new Date(2021, 0, 1), 150.5, null],
+            [new Date(2022, 0, 1), null, null]
+        ]);
+        other.addRows([
+            [new Date(2021, 0, 1), 1.0, null]
+        ]);
+    </script></body></html>"#;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn chart_rows() {
+        let mut history = PriceHistory::new();
+        history.extend_from_page("10300-1", &Html::parse_document(PAGE));
+
+        // javascript months start at 0 and Feb 30 doesn't exist
+        assert_eq!(
+            history.date,
+            vec![
+                date(2021, 1, 1),
+                date(2021, 6, 15),
+                date(2022, 1, 1),
+                date(2022, 12, 31)
+            ]
+        );
+        assert_eq!(history.set_number, vec!["10300-1"; 4]);
+        assert_eq!(
+            history.value_new,
+            vec![Some(19999), Some(5500), None, Some(129900)]
+        );
+        // the third chart isn't a value chart
+        assert_eq!(history.value_used, vec![Some(15050), None, None, None]);
+    }
+}
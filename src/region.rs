@@ -0,0 +1,46 @@
+//! Sales regions that brickeconomy lists prices for
+
+use crate::money::Currency;
+
+use clap::ValueEnum;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Region {
+    #[default]
+    Us,
+    Uk,
+    Eu,
+    Ca,
+}
+
+impl Region {
+    // what gets stored in the region column
+    pub fn code(&self) -> &'static str {
+        match self {
+            Region::Us => "US",
+            Region::Uk => "UK",
+            Region::Eu => "EU",
+            Region::Ca => "CA",
+        }
+    }
+
+    // how the region is labeled in the sales region table
+    pub fn sales_region_label(&self) -> &'static str {
+        match self {
+            Region::Us => "United States",
+            Region::Uk => "United Kingdom",
+            Region::Eu => "Europe",
+            Region::Ca => "Canada",
+        }
+    }
+
+    // prices for the region are in the local currency
+    pub fn currency(&self) -> Currency {
+        match self {
+            Region::Us => Currency::Usd,
+            Region::Uk => Currency::Gbp,
+            Region::Eu => Currency::Eur,
+            Region::Ca => Currency::Cad,
+        }
+    }
+}
@@ -0,0 +1,18 @@
+//! Reading scan results back in from legot.csv and the daemon's dated files
+
+use polars::prelude::*;
+use std::fs::File;
+
+pub fn read_results(path: &str) -> DataFrame {
+    // barcodes can have leading zeros
+    let mut schema = Schema::new();
+    schema.with_column("upc".to_string(), DataType::Utf8);
+    schema.with_column("ean".to_string(), DataType::Utf8);
+    schema.with_column("item_number".to_string(), DataType::Utf8);
+
+    CsvReader::new(File::open(path).unwrap_or_else(|_| panic!("A connection to {path}.")))
+        .with_dtypes(Some(&schema))
+        .has_header(true)
+        .finish()
+        .unwrap_or_else(|_| panic!("A polars DataFrame from {path}."))
+}
@@ -0,0 +1,47 @@
+//! Ranking deals by a score column
+//!
+//! every strategy scores so that a higher score is a better deal
+
+use clap::ValueEnum;
+use polars::prelude::*;
+
+#[derive(Clone, Copy, Debug, Default, ValueEnum)]
+pub enum ScoringStrategy {
+    /// value - listed_price
+    AbsoluteDiscount,
+    /// (value - listed_price) / value
+    PercentDiscount,
+    /// (value - listed_price) / (value * pieces)
+    #[default]
+    PerPiece,
+    /// expected_profit, i.e. value after resale fees - landed_cost
+    ExpectedProfit,
+    /// (value - retail_price) / retail_price, how much a set has grown since release
+    ValueGrowth,
+    /// cagr, (value / retail_price)^(1 / years since release) - 1
+    Cagr,
+    /// -listed_vs_benchmark, how far below its theme and year's value per piece a listing is
+    BenchmarkDiscount,
+}
+
+impl ScoringStrategy {
+    /// the score column; needs the CostModel columns for ExpectedProfit and
+    /// the investment columns for Cagr and the benchmark columns for
+    /// BenchmarkDiscount
+    pub fn score(self) -> Expr {
+        let discount = col("value") - col("listed_price");
+        let score = match self {
+            ScoringStrategy::AbsoluteDiscount => discount,
+            ScoringStrategy::PercentDiscount => discount / col("value"),
+            ScoringStrategy::PerPiece => discount / (col("value") * col("pieces")),
+            ScoringStrategy::ExpectedProfit => col("expected_profit"),
+            ScoringStrategy::ValueGrowth => {
+                (col("value") - col("retail_price")) / col("retail_price")
+            }
+            ScoringStrategy::Cagr => col("cagr"),
+            ScoringStrategy::BenchmarkDiscount => lit(0.0) - col("listed_vs_benchmark"),
+        };
+
+        score.alias("score")
+    }
+}
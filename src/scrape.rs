@@ -0,0 +1,409 @@
+//! Scraping a set's page on brickeconomy
+
+use crate::money::{parse_money_in, Currency};
+use crate::price_history::PriceHistory;
+use crate::region::Region;
+use crate::scraper_utils::{make_selector, throttle};
+use crate::set_data::SetData;
+use crate::text::element_text;
+
+use chrono::Local;
+use color_eyre::eyre::eyre;
+use lazy_static::lazy_static;
+use regex::Regex;
+use scraper::{Html, Selector};
+
+lazy_static! {
+    // create selectors
+    static ref TABLE: Selector = make_selector("table");
+    static ref TR: Selector = make_selector("tr");
+    static ref TD: Selector = make_selector("td");
+    static ref H4: Selector = make_selector("h4");
+    static ref H4_A: Selector = make_selector("h4 a");
+    static ref SET_DETAILS: Selector = make_selector("div#SetDetails div.row");
+    static ref COL_XS_5: Selector = make_selector("div.col-xs-5");
+    static ref COL_XS_7: Selector = make_selector("div.col-xs-7");
+    static ref TABLE_TR_TD_H1: Selector = make_selector("table tr td h1");
+    // gets listed prices; one row per sales region
+    static ref SALES_REGION_ROWS: Selector = make_selector("table#sales_region_table tr");
+    static ref TD_DIV_SPAN_A: Selector = make_selector("td div span.a");
+    // these literally says 'placeholder' so this might break
+    static ref PRICE_ROWS_SELECTOR: Selector = make_selector("#ContentPlaceHolder1_PanelSetPricing div.row");
+    // value is nested under a hover
+    static ref SPAN_HELPPOPOVER: Selector = make_selector("span.helppopover");
+
+    // create regular expressions
+    static ref RE_PIECE_COUNT: Regex = Regex::new(r"(\d+(?:,\d{3})*)").expect("A Regex of a piece count.");
+    static ref RE_DIMENSIONS_CM: Regex = Regex::new(r"(?i)([\d.]+)\s*x\s*([\d.]+)\s*x\s*([\d.]+)\s*cm").expect("A Regex of box dimensions in cm.");
+    static ref RE_DIMENSIONS_IN: Regex = Regex::new(r"(?i)([\d.]+)\s*x\s*([\d.]+)\s*x\s*([\d.]+)\s*in").expect("A Regex of box dimensions in inches.");
+    static ref RE_WEIGHT_KG: Regex = Regex::new(r"(?i)([\d.]+)\s*kg").expect("A Regex of a weight in kg.");
+    static ref RE_WEIGHT_LB: Regex = Regex::new(r"(?i)([\d.]+)\s*lbs?").expect("A Regex of a weight in lb.");
+    static ref RE_BARCODE: Regex = Regex::new(r"\b(\d{12,13})\b").expect("A Regex of a UPC or EAN barcode.");
+    static ref RE_ITEM_NUMBER: Regex = Regex::new(r"\d+").expect("A Regex of a LEGO item number.");
+    // e.g. "9-14" or "18+"
+    static ref RE_AGE_RANGE: Regex = Regex::new(r"(\d+)\s*(?:\+|(?:-|–|to)\s*(\d+))").expect("A Regex of an age range.");
+    static ref RE_YEAR: Regex = Regex::new(r"\b(\d{4})\b").expect("A Regex for a 4 digit number.");
+}
+
+/// fetch a set's page and push what it lists onto `set_data`
+///
+/// every column of `set_data` is the same length afterwards except for the
+/// ones caught up by SetData::into_dataframe(). Nothing is pushed when the
+/// page can't be fetched, e.g. for a set number that doesn't exist
+pub async fn scrape_set(
+    client: &reqwest::Client,
+    set_number: &str,
+    region: Region,
+    set_data: &mut SetData,
+    price_history: Option<&mut PriceHistory>,
+) -> color_eyre::Result<()> {
+    let url = format!("https://www.brickeconomy.com/set/{}/", set_number);
+
+    // TODO: is there a way to get this to play nice with async? Maybe with a tower
+    // service?
+    throttle();
+    let response = client.get(url).send().await?;
+
+    match response.status() {
+        reqwest::StatusCode::OK => {
+            let content = response
+                .text()
+                .await
+                .expect("The text of the get response.");
+            let document = Html::parse_document(&content);
+
+            if let Some(price_history) = price_history {
+                price_history.extend_from_page(set_number, &document);
+            }
+
+            let set_details = document.select(&SET_DETAILS);
+
+            // Catch all other edge cases so that the columns are the same len
+            // sometimes the header isn't even there, not sure if forcing it is the best
+            if set_data.set_number.len() > set_data.pieces.len() {
+                set_data.pieces.push(None);
+            }
+            assert_eq!(
+                &set_data.set_number.len(),
+                &set_data.pieces.len(),
+                "Set number and pieces columns aren't the same length after set #{:?}.",
+                set_data
+                    .set_number
+                    .last()
+                    .expect("The last value of set_data.set_number.")
+            );
+            // sometimes the value isn't there
+            if set_data.set_number.len() > set_data.value.len() {
+                set_data.value.push(None);
+                println!(
+                    "last set: {:?}",
+                    set_data
+                        .set_number
+                        .last()
+                        .expect("The last value in set_data.set_number.")
+                )
+            }
+            assert_eq!(
+                &set_data.set_number.len(),
+                &set_data.value.len(),
+                "Set number and pieces columns aren't the same length after set #{:?}.",
+                set_data
+                    .set_number
+                    .last()
+                    .expect("The last value of set_data.set_number.")
+            );
+            // sometimes there just isn't a place to get the retail price
+            if set_data.set_number.len() > set_data.retail_price.len() {
+                set_data.retail_price.push(None);
+            }
+            assert_eq!(
+                &set_data.set_number.len(),
+                &set_data.retail_price.len(),
+                "Set number and retail_price columns aren't the same length after set #{:?}.",
+                set_data
+                    .set_number
+                    .last()
+                    .expect("The last value of set_data.set_number.")
+            );
+
+            // push one item at a time incase there are multiple
+            // push set number (as a string because of the '-')
+            if set_data.set_number.len() == set_data.name.len() {
+                for detail in set_details {
+                    let mut header = detail.select(&COL_XS_5);
+                    let mut item = detail.select(&COL_XS_7);
+
+                    // sometimes a header is repeated; e.g. new and used Value
+                    let mut value_header_count = 0;
+
+                    if let Some(header) = header.next() {
+                        let header = element_text(&header);
+                        match header.as_str() {
+                            "Set number" => set_data.set_number.push(element_text(
+                                &item.next().expect("The next set number from set details."),
+                            )),
+                            "Name" => {
+                                set_data.name.push(element_text(
+                                    &item.next().expect("The next name from set details."),
+                                ));
+                            }
+                            "Year" => {
+                                if let Some(year) = item.next() {
+                                    let year = element_text(&year);
+                                    let numbers = RE_YEAR.captures(&year);
+                                    let numbers = numbers
+                                        .expect("The matches of a regex for 4 digit numbers.");
+                                    let year = &numbers[1];
+                                    set_data.year.push(year.parse::<i32>().ok());
+                                } else {
+                                    set_data.year.push(None);
+                                }
+                            }
+                            "Theme" | "Subtheme" => {
+                                let theme = item
+                                    .next()
+                                    .map(|theme| element_text(&theme))
+                                    .filter(|theme| !theme.is_empty());
+                                let column = if header.as_str() == "Theme" {
+                                    set_data.theme.last_mut()
+                                } else {
+                                    set_data.subtheme.last_mut()
+                                };
+                                if let Some(column) = column {
+                                    *column = theme;
+                                }
+                            }
+                            "Pieces" => {
+                                if let Some(pieces) = item.next() {
+                                    let piece_count = element_text(&pieces);
+                                    let numbers = RE_PIECE_COUNT.captures(&piece_count);
+                                    let numbers =
+                                        numbers.expect("The matches of a regex for a piece count.");
+                                    let piece_count = numbers[1].split(',').collect::<String>();
+                                    if let Ok(count) = piece_count.parse::<u32>() {
+                                        set_data.pieces.push(Some(count));
+                                    } else {
+                                        set_data.pieces.push(None);
+                                    }
+                                } else {
+                                    set_data.pieces.push(None);
+                                }
+                            }
+                            "Dimensions" => {
+                                let dimensions = item
+                                    .next()
+                                    .map(|dimensions| element_text(&dimensions))
+                                    .and_then(|dimensions| parse_dimensions_cm(&dimensions));
+                                set_data.dimensions_cm.push(dimensions);
+                            }
+                            "UPC" | "EAN" | "Barcode" | "Barcodes" => {
+                                let barcodes = item
+                                    .next()
+                                    .map(|barcodes| element_text(&barcodes))
+                                    .unwrap_or_default();
+                                for barcode in RE_BARCODE.find_iter(&barcodes) {
+                                    let barcode = barcode.as_str();
+                                    let column = if barcode.len() == 12 {
+                                        set_data.upc.last_mut()
+                                    } else {
+                                        set_data.ean.last_mut()
+                                    };
+                                    if let Some(column) = column {
+                                        *column = Some(barcode.to_string());
+                                    }
+                                }
+                            }
+                            "LEGO item number" | "Item number" => {
+                                let item_number = item
+                                    .next()
+                                    .map(|item_number| element_text(&item_number))
+                                    .and_then(|item_number| {
+                                        RE_ITEM_NUMBER
+                                            .find(&item_number)
+                                            .map(|number| number.as_str().to_string())
+                                    });
+                                if let Some(column) = set_data.item_number.last_mut() {
+                                    *column = item_number;
+                                }
+                            }
+                            "Ages" | "Age" | "Age range" => {
+                                let ages = item
+                                    .next()
+                                    .map(|ages| element_text(&ages))
+                                    .unwrap_or_default();
+                                if let Some(ages) = RE_AGE_RANGE.captures(&ages) {
+                                    if let Some(column) = set_data.min_age.last_mut() {
+                                        *column = ages[1].parse::<u32>().ok();
+                                    }
+                                    if let Some(column) = set_data.max_age.last_mut() {
+                                        *column = ages
+                                            .get(2)
+                                            .and_then(|age| age.as_str().parse::<u32>().ok());
+                                    }
+                                }
+                            }
+                            "Weight" => {
+                                let weight = item
+                                    .next()
+                                    .map(|weight| element_text(&weight))
+                                    .and_then(|weight| parse_weight_kg(&weight));
+                                set_data.weight_kg.push(weight);
+                            }
+                            _ => continue,
+                        }
+
+                        // push other items only once per valid set number
+                        if header.as_str() == "Set number" {
+                            // filled in by their own rows if the page has them
+                            set_data.theme.push(None);
+                            set_data.subtheme.push(None);
+                            set_data.value_used.push(None);
+                            set_data.upc.push(None);
+                            set_data.ean.push(None);
+                            set_data.item_number.push(None);
+                            set_data.min_age.push(None);
+                            set_data.max_age.push(None);
+
+                            set_data.region.push(region.code().to_string());
+                            set_data.currency.push(region.currency().code().to_string());
+                            set_data.scanned_on.push(Local::now().date_naive());
+
+                            // push listed price
+                            let region_rows: Vec<_> = document.select(&SALES_REGION_ROWS).collect();
+                            let region_row = region_rows
+                                .iter()
+                                .find(|row| {
+                                    row.text()
+                                        .any(|text| text.contains(region.sales_region_label()))
+                                })
+                                // US listings come first and aren't always labeled,
+                                // but a header row can come before them
+                                .or_else(|| match region {
+                                    Region::Us => region_rows
+                                        .iter()
+                                        .find(|row| row.select(&TD_DIV_SPAN_A).next().is_some()),
+                                    _ => None,
+                                });
+                            let price = region_row
+                                .and_then(|row| row.select(&TD_DIV_SPAN_A).next())
+                                .map(|price| element_text(&price))
+                                .and_then(|price| parse_money_in(&price, region.currency()));
+                            set_data.listed_price.push(price.map(|price| price.cents));
+
+                            // push prices
+                            let price_rows = document.select(&PRICE_ROWS_SELECTOR);
+                            for row in price_rows {
+                                let headers = row.select(&COL_XS_5);
+                                let mut items = row.select(&COL_XS_7);
+
+                                for header in headers {
+                                    let mut header_text = element_text(&header);
+                                    let item = items.next();
+
+                                    // some headers are further nested
+                                    let value_headers = header.select(&SPAN_HELPPOPOVER);
+                                    for header in value_headers {
+                                        header_text = element_text(&header);
+                                    }
+
+                                    match header_text.as_str() {
+                                        "Retail price" => {
+                                            let price = item.and_then(|price| {
+                                                parse_money_in(
+                                                    &element_text(&price),
+                                                    region.currency(),
+                                                )
+                                            });
+                                            set_data
+                                                .retail_price
+                                                .push(price.map(|price| price.cents));
+                                        }
+                                        // as either market price or brickeconomy estimate
+                                        // depending if the set is still availible at retail
+                                        // brickeconomy values are always in US dollars
+                                        "Value" | "Market price" => {
+                                            // sometimes there are both new and used
+                                            // values; new seems to be first
+                                            value_header_count += 1;
+                                            if value_header_count == 1 {
+                                                if let Some(price) = item {
+                                                    // sometimes there is an additional
+                                                    // <b> nested
+                                                    let price = element_text(&price);
+                                                    let price =
+                                                        parse_money_in(&price, Currency::Usd);
+                                                    set_data
+                                                        .value
+                                                        .push(price.map(|price| price.cents));
+                                                }
+                                            } else if value_header_count == 2 {
+                                                let price = item.and_then(|price| {
+                                                    parse_money_in(
+                                                        &element_text(&price),
+                                                        Currency::Usd,
+                                                    )
+                                                });
+                                                if let Some(column) = set_data.value_used.last_mut()
+                                                {
+                                                    *column = price.map(|price| price.cents);
+                                                }
+                                            }
+                                        }
+                                        _ => {}
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        problem => {
+            return Err(eyre!("set #{set_number}: {problem}"));
+        }
+    }
+    // sometimes the value isn't there
+    if set_data.set_number.len() > set_data.value.len() {
+        set_data.value.push(None);
+    }
+    // older sets rarely list a box size or weight
+    if set_data.set_number.len() > set_data.dimensions_cm.len() {
+        set_data.dimensions_cm.push(None);
+    }
+    if set_data.set_number.len() > set_data.weight_kg.len() {
+        set_data.weight_kg.push(None);
+    }
+    // need to catch if the html is being missed somewhere
+    // TODO: could probably clean up some alerts
+    assert_eq!(
+        &set_data.set_number.len(),
+        &set_data.year.len(),
+        "Set number and year columns aren't the same length after set #{:?}.",
+        set_data
+            .set_number
+            .last()
+            .expect("The last value in set_data.set_number.")
+    );
+
+    Ok(())
+}
+
+// box dimensions as length, width and height in cm
+fn parse_dimensions_cm(text: &str) -> Option<[f64; 3]> {
+    let (captures, to_cm) = match RE_DIMENSIONS_CM.captures(text) {
+        Some(captures) => (captures, 1.0),
+        None => (RE_DIMENSIONS_IN.captures(text)?, 2.54),
+    };
+    let dimension = |index: usize| captures[index].parse::<f64>().ok().map(|d| d * to_cm);
+
+    Some([dimension(1)?, dimension(2)?, dimension(3)?])
+}
+
+fn parse_weight_kg(text: &str) -> Option<f64> {
+    if let Some(captures) = RE_WEIGHT_KG.captures(text) {
+        captures[1].parse::<f64>().ok()
+    } else {
+        let captures = RE_WEIGHT_LB.captures(text)?;
+        captures[1].parse::<f64>().ok().map(|lb| lb * 0.453_592_37)
+    }
+}
@@ -1,3 +1,8 @@
+use crate::money::cents_to_units;
+
+use chrono::NaiveDate;
+use polars::prelude::*;
+
 // where the data we pull will be stored
 pub struct SetData {
     // can't be a number because it's formatted with a '-'
@@ -5,19 +10,54 @@ pub struct SetData {
 
     pub name: Vec<String>,
 
-    pub year: Vec<Option<String>>,
+    pub theme: Vec<Option<String>>,
+
+    pub subtheme: Vec<Option<String>>,
 
-    pub retail_price: Vec<Option<f32>>,
+    pub year: Vec<Option<i32>>,
+
+    // prices are in cents of their currency; see crate::money
+    pub retail_price: Vec<Option<i64>>,
 
     // either market price or brickeconomy estimate
-    pub value: Vec<Option<f32>>,
+    pub value: Vec<Option<i64>>,
+
+    // the value of an opened or used set, when brickeconomy lists one
+    pub value_used: Vec<Option<i64>>,
 
     // a seller's price; should be cheapest but not guaranteed
-    pub listed_price: Vec<Option<f32>>,
+    pub listed_price: Vec<Option<i64>>,
+
+    // u16 (65_535) would cover the current largest set at 11_695 pieces
+    // but polars::prelude::NamedFrom isn't impled for Vec<Option<u16>>
+    pub pieces: Vec<Option<u32>>,
+
+    // length, width and height of the box
+    pub dimensions_cm: Vec<Option<[f64; 3]>>,
+
+    pub weight_kg: Vec<Option<f64>>,
+
+    // barcodes as text since they can have leading zeros
+    pub upc: Vec<Option<String>>,
+
+    pub ean: Vec<Option<String>>,
+
+    // LEGO's own item number for the boxed set
+    pub item_number: Vec<Option<String>>,
+
+    // max_age is None for ratings like "18+"
+    pub min_age: Vec<Option<u32>>,
 
-    // u16 (65_535) since current largest set is only 11_695 pieces
-    // not u16 because polars::perlude::NamedFrom isn't impled for Vec<Option<u16>>?
-    pub pieces: Vec<Option<f32>>,
+    pub max_age: Vec<Option<u32>>,
+
+    // the sales region the listed and retail prices came from
+    pub region: Vec<String>,
+
+    // the currency of the listed and retail prices; values are always USD
+    pub currency: Vec<String>,
+
+    // the day the set page was scraped
+    pub scanned_on: Vec<NaiveDate>,
 }
 
 impl SetData {
@@ -25,11 +65,193 @@ impl SetData {
         SetData {
             set_number: vec![],
             name: vec![],
+            theme: vec![],
+            subtheme: vec![],
             year: vec![],
             retail_price: vec![],
             value: vec![],
+            value_used: vec![],
             listed_price: vec![],
             pieces: vec![],
+            dimensions_cm: vec![],
+            weight_kg: vec![],
+            upc: vec![],
+            ean: vec![],
+            item_number: vec![],
+            min_age: vec![],
+            max_age: vec![],
+            region: vec![],
+            currency: vec![],
+            scanned_on: vec![],
+        }
+    }
+
+    pub fn into_dataframe(mut self) -> DataFrame {
+        // sometimes there just isn't a place to get the data and
+        // we're on the last get request so the manual push misses
+        if self.set_number.len() > self.retail_price.len() {
+            self.retail_price.push(None);
         }
+        if self.set_number.len() > self.value.len() {
+            self.value.push(None);
+        }
+        if self.set_number.len() > self.pieces.len() {
+            self.pieces.push(None);
+        }
+
+        // make sure the index len is the same before we make a dataframe
+        assert_eq!(
+            &self.set_number.len(),
+            &self.name.len(),
+            "Set number and name columns aren't the same length."
+        );
+        assert_eq!(
+            &self.set_number.len(),
+            &self.theme.len(),
+            "Set number and theme columns aren't the same length."
+        );
+        assert_eq!(
+            &self.set_number.len(),
+            &self.subtheme.len(),
+            "Set number and subtheme columns aren't the same length."
+        );
+        assert_eq!(
+            &self.set_number.len(),
+            &self.year.len(),
+            "Set number and year columns aren't the same length."
+        );
+        assert_eq!(
+            &self.set_number.len(),
+            &self.retail_price.len(),
+            "Set number and retail price columns aren't the same length."
+        );
+        assert_eq!(
+            &self.set_number.len(),
+            &self.value.len(),
+            "Set number and value columns aren't the same length."
+        );
+        assert_eq!(
+            &self.set_number.len(),
+            &self.value_used.len(),
+            "Set number and used value columns aren't the same length."
+        );
+        assert_eq!(
+            &self.set_number.len(),
+            &self.listed_price.len(),
+            "Set number and listed price columns aren't the same length."
+        );
+        assert_eq!(
+            &self.set_number.len(),
+            &self.dimensions_cm.len(),
+            "Set number and dimensions columns aren't the same length."
+        );
+        assert_eq!(
+            &self.set_number.len(),
+            &self.weight_kg.len(),
+            "Set number and weight columns aren't the same length."
+        );
+        assert_eq!(
+            &self.set_number.len(),
+            &self.upc.len(),
+            "Set number and upc columns aren't the same length."
+        );
+        assert_eq!(
+            &self.set_number.len(),
+            &self.ean.len(),
+            "Set number and ean columns aren't the same length."
+        );
+        assert_eq!(
+            &self.set_number.len(),
+            &self.item_number.len(),
+            "Set number and item number columns aren't the same length."
+        );
+        assert_eq!(
+            &self.set_number.len(),
+            &self.min_age.len(),
+            "Set number and min age columns aren't the same length."
+        );
+        assert_eq!(
+            &self.set_number.len(),
+            &self.max_age.len(),
+            "Set number and max age columns aren't the same length."
+        );
+        assert_eq!(
+            &self.set_number.len(),
+            &self.region.len(),
+            "Set number and region columns aren't the same length."
+        );
+        assert_eq!(
+            &self.set_number.len(),
+            &self.currency.len(),
+            "Set number and currency columns aren't the same length."
+        );
+        assert_eq!(
+            &self.set_number.len(),
+            &self.scanned_on.len(),
+            "Set number and scanned on columns aren't the same length."
+        );
+        assert_eq!(
+            &self.set_number.len(),
+            &self.pieces.len(),
+            "Set number and pieces columns aren't the same length after set #{:?}.",
+            self.set_number
+                .last()
+                .expect("The last value of self.set_number.")
+        );
+
+        let s_set_number = Series::new("set_number", &self.set_number);
+        let s_name = Series::new("name", &self.name);
+        let s_theme = Series::new("theme", &self.theme);
+        let s_subtheme = Series::new("subtheme", &self.subtheme);
+        let s_year = Series::new("year", &self.year);
+        let s_retail_price = Series::new("retail_price", cents_to_units(&self.retail_price));
+        let s_value = Series::new("value", cents_to_units(&self.value));
+        let s_value_used = Series::new("value_used", cents_to_units(&self.value_used));
+        let s_listed_price = Series::new("listed_price", cents_to_units(&self.listed_price));
+        let s_pieces = Series::new("pieces", &self.pieces);
+        let dimension = |index: usize| -> Vec<Option<f64>> {
+            self.dimensions_cm
+                .iter()
+                .map(|dimensions| dimensions.map(|dimensions| dimensions[index]))
+                .collect()
+        };
+        let s_length = Series::new("length_cm", dimension(0));
+        let s_width = Series::new("width_cm", dimension(1));
+        let s_height = Series::new("height_cm", dimension(2));
+        let s_weight = Series::new("weight_kg", &self.weight_kg);
+        let s_upc = Series::new("upc", &self.upc);
+        let s_ean = Series::new("ean", &self.ean);
+        let s_item_number = Series::new("item_number", &self.item_number);
+        let s_min_age = Series::new("min_age", &self.min_age);
+        let s_max_age = Series::new("max_age", &self.max_age);
+        let s_region = Series::new("region", &self.region);
+        let s_currency = Series::new("currency", &self.currency);
+        let s_scanned_on = Series::new("scanned_on", &self.scanned_on);
+
+        DataFrame::new(vec![
+            s_set_number,
+            s_name,
+            s_theme,
+            s_subtheme,
+            s_year,
+            s_retail_price,
+            s_value,
+            s_value_used,
+            s_listed_price,
+            s_pieces,
+            s_length,
+            s_width,
+            s_height,
+            s_weight,
+            s_upc,
+            s_ean,
+            s_item_number,
+            s_min_age,
+            s_max_age,
+            s_region,
+            s_currency,
+            s_scanned_on,
+        ])
+        .expect("A Polars DataFrame.")
     }
 }
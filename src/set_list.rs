@@ -0,0 +1,134 @@
+//! Reading and writing the stored list of valid sets
+
+use polars::prelude::*;
+use std::fs::File;
+
+pub const SET_LIST_PATH: &str = "set_list.csv";
+
+// the columns of the set list in the order they're written
+pub const SET_LIST_COLUMNS: [&str; 9] = [
+    "set_number",
+    "name",
+    "theme",
+    "subtheme",
+    "year",
+    "pieces",
+    "upc",
+    "ean",
+    "item_number",
+];
+
+/// set numbers are stored with their variant, e.g. 10300 is 10300-1
+pub fn with_variant(set_number: &str) -> String {
+    if set_number.contains('-') {
+        set_number.to_string()
+    } else {
+        format!("{set_number}-1")
+    }
+}
+
+// older set lists were written with year as text and pieces as a float
+// so read loosely and let migrate() cast to the current types
+fn read_schema() -> Schema {
+    let mut schema = Schema::new();
+    schema.with_column("set_number".to_string(), DataType::Utf8);
+    schema.with_column("name".to_string(), DataType::Utf8);
+    schema.with_column("theme".to_string(), DataType::Utf8);
+    schema.with_column("subtheme".to_string(), DataType::Utf8);
+    schema.with_column("year".to_string(), DataType::Utf8);
+    schema.with_column("pieces".to_string(), DataType::Float64);
+    // barcodes can have leading zeros
+    schema.with_column("upc".to_string(), DataType::Utf8);
+    schema.with_column("ean".to_string(), DataType::Utf8);
+    schema.with_column("item_number".to_string(), DataType::Utf8);
+    schema
+}
+
+/// cast year and pieces from the types older csvs used to the current ones
+pub fn migrate(lf: LazyFrame) -> LazyFrame {
+    lf.with_columns([
+        col("year").cast(DataType::Int32),
+        col("pieces").cast(DataType::UInt32),
+    ])
+}
+
+pub fn read_set_list() -> DataFrame {
+    read_set_list_from(SET_LIST_PATH)
+}
+
+fn read_set_list_from(path: &str) -> DataFrame {
+    let mut df = CsvReader::from_path(path)
+        .expect("A reader connection to set_list.csv")
+        .with_dtypes(Some(&read_schema()))
+        .has_header(true)
+        .finish()
+        .expect("A polars DataFrame from set_list.csv");
+
+    // older set lists only had set_number, year and pieces
+    for column in SET_LIST_COLUMNS {
+        if df.column(column).is_err() {
+            let missing = Series::full_null(column, df.height(), &DataType::Utf8);
+            df.with_column(missing)
+                .expect("A null column added to the set list.");
+        }
+    }
+
+    migrate(df.lazy())
+        .select(SET_LIST_COLUMNS.map(col))
+        .collect()
+        .expect("The set list with current dtypes.")
+}
+
+pub fn write_set_list(set_list_df: &mut DataFrame) {
+    let set_list = File::create(SET_LIST_PATH).expect("The creation of the set_list.csv");
+    let mut writer: CsvWriter<File> = CsvWriter::new(set_list).has_header(true);
+    writer
+        .finish(set_list_df)
+        .expect("The writting of our data to set_list.csv");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(name: &str, csv: &str) -> DataFrame {
+        let path = std::env::temp_dir()
+            .join(format!("leget-{name}-{}.csv", std::process::id()))
+            .to_string_lossy()
+            .into_owned();
+        std::fs::write(&path, csv).unwrap();
+        let df = read_set_list_from(&path);
+        std::fs::remove_file(&path).unwrap();
+        df
+    }
+
+    #[test]
+    fn old_set_lists_are_migrated() {
+        let df = read(
+            "old-set-list",
+            "set_number,year,pieces\n10300-1,2022,1872.0\n75192-1,2017,7541.0\n",
+        );
+
+        assert_eq!(df.get_column_names(), SET_LIST_COLUMNS);
+        assert_eq!(df.column("year").unwrap().dtype(), &DataType::Int32);
+        assert_eq!(df.column("pieces").unwrap().dtype(), &DataType::UInt32);
+        assert_eq!(df.column("pieces").unwrap().get(1), AnyValue::UInt32(7541));
+        assert_eq!(df.column("name").unwrap().null_count(), 2);
+    }
+
+    #[test]
+    fn names_stay_text() {
+        let df = read(
+            "set-list-names",
+            "set_number,name,theme,subtheme,year,pieces,upc,ean,item_number\n\
+             2048-1,2048,Seasonal,,2019,100,012345678905,,6251234\n",
+        );
+
+        assert_eq!(df.column("name").unwrap().dtype(), &DataType::Utf8);
+        assert_eq!(df.column("name").unwrap().get(0), AnyValue::Utf8("2048"));
+        assert_eq!(
+            df.column("upc").unwrap().get(0),
+            AnyValue::Utf8("012345678905")
+        );
+    }
+}
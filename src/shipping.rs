@@ -0,0 +1,161 @@
+//! Estimating shipping costs from a set's weight and box size
+//!
+//! The rates file is a csv of weight tiers with columns `max_weight_kg,cost`.
+//! A set ships at the cost of the first tier its billable weight fits under,
+//! where billable weight is the larger of the actual weight and the
+//! dimensional weight (box volume in cm³ divided by the divisor). Sets
+//! heavier than the last tier can't ship at these rates and are left out.
+//!
+//! ```text
+//! max_weight_kg,cost
+//! 1.0,9.50
+//! 2.5,14.75
+//! 5.0,21.00
+//! ```
+
+use polars::prelude::*;
+
+pub struct ShippingRates {
+    // sorted by max weight
+    tiers: Vec<(f64, f64)>,
+    // cm³ per kg
+    dim_divisor: f64,
+}
+
+impl ShippingRates {
+    pub fn from_path(path: &str, dim_divisor: f64) -> Self {
+        let mut schema = Schema::new();
+        schema.with_column("max_weight_kg".to_string(), DataType::Float64);
+        schema.with_column("cost".to_string(), DataType::Float64);
+
+        let df = CsvReader::from_path(path)
+            .expect("A reader connection to the shipping rates file.")
+            .with_dtypes(Some(&schema))
+            .has_header(true)
+            .finish()
+            .expect("A polars DataFrame from the shipping rates file.");
+
+        let max_weights = df
+            .column("max_weight_kg")
+            .expect("The max_weight_kg column of the shipping rates.")
+            .f64()
+            .expect("Max weights as f64.");
+        let costs = df
+            .column("cost")
+            .expect("The cost column of the shipping rates.")
+            .f64()
+            .expect("Costs as f64.");
+
+        let mut tiers: Vec<(f64, f64)> = max_weights
+            .into_no_null_iter()
+            .zip(costs.into_no_null_iter())
+            .collect();
+        assert!(
+            !tiers.is_empty(),
+            "The shipping rates file {path} has no tiers."
+        );
+        tiers.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        ShippingRates { tiers, dim_divisor }
+    }
+
+    /// the larger of the actual and dimensional weight; null if neither is known
+    fn billable_weight(&self) -> Expr {
+        let dim_weight =
+            col("length_cm") * col("width_cm") * col("height_cm") / lit(self.dim_divisor);
+        let weight = col("weight_kg").fill_null(dim_weight.clone());
+        let dim_weight = dim_weight.fill_null(col("weight_kg"));
+
+        when(weight.clone().gt(dim_weight.clone()))
+            .then(weight)
+            .otherwise(dim_weight)
+    }
+
+    /// whether a set fits under the heaviest tier; true when its weight is unknown
+    pub fn fits(&self) -> Expr {
+        let (heaviest, _) = self.tiers[self.tiers.len() - 1];
+        let weight = self.billable_weight();
+
+        weight.clone().is_null().or(weight.lt_eq(lit(heaviest)))
+    }
+
+    /// an estimated shipping_cost; null when too heavy for the table or unknown
+    pub fn shipping_cost(&self) -> Expr {
+        let weight = self.billable_weight();
+
+        // nest from the heaviest tier out so the lightest tier is checked first
+        let mut cost = lit(NULL).cast(DataType::Float64);
+        for (max_weight, tier_cost) in self.tiers.iter().rev() {
+            cost = when(weight.clone().lt_eq(lit(*max_weight)))
+                .then(lit(*tier_cost))
+                .otherwise(cost);
+        }
+        cost.alias("shipping_cost")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tiers_and_dimensional_weight() {
+        let rates = ShippingRates {
+            tiers: vec![(1.0, 9.5), (2.5, 14.75), (5.0, 21.0)],
+            dim_divisor: 5000.0,
+        };
+        let df = df!(
+            "weight_kg" => [Some(1.0), Some(1.01), Some(2.5), Some(5.0), Some(5.01), Some(0.5), None, None],
+            // 40 x 25 x 10 cm is 2 kg of dimensional weight
+            "length_cm" => [None, None, None, None, None, Some(40.0), Some(40.0), None],
+            "width_cm" => [None, None, None, None, None, Some(25.0), Some(25.0), None],
+            "height_cm" => [None, None, None, None, None, Some(10.0), Some(10.0), None]
+        )
+        .unwrap()
+        .lazy()
+        .select([rates.shipping_cost(), rates.fits().alias("fits")])
+        .collect()
+        .unwrap();
+
+        let costs: Vec<Option<f64>> = df
+            .column("shipping_cost")
+            .unwrap()
+            .f64()
+            .unwrap()
+            .into_iter()
+            .collect();
+        assert_eq!(
+            costs,
+            [
+                Some(9.5),
+                Some(14.75),
+                Some(14.75),
+                Some(21.0),
+                None,
+                Some(14.75),
+                Some(14.75),
+                None
+            ]
+        );
+        let fits: Vec<Option<bool>> = df
+            .column("fits")
+            .unwrap()
+            .bool()
+            .unwrap()
+            .into_iter()
+            .collect();
+        assert_eq!(
+            fits,
+            [
+                Some(true),
+                Some(true),
+                Some(true),
+                Some(true),
+                Some(false),
+                Some(true),
+                Some(true),
+                Some(true)
+            ]
+        );
+    }
+}
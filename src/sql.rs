@@ -0,0 +1,225 @@
+//! Ad hoc SQL over scan results, the set list and the scan history
+//!
+//! The query runs in an in-memory SQLite database with these tables:
+//!
+//! - `sets`: the latest scan results, legot.csv or --output
+//! - `previous_sets`: every dated daemon output of those, e.g.
+//!   legot-YYYY-MM-DD.csv, with the file each row came from in `file`
+//! - `set_list`: set_list.csv
+//! - `scans` and `records`: the leget.db history, read only
+
+use crate::daemon::dated_outputs;
+use crate::history::HISTORY_DB_PATH;
+use crate::results::read_results;
+use crate::set_list::{read_set_list, SET_LIST_PATH};
+
+use chrono::NaiveDate;
+use clap::ValueEnum;
+use polars::prelude::*;
+use rusqlite::types::{Value, ValueRef};
+use rusqlite::{params_from_iter, Connection};
+use std::path::Path;
+
+/// how `leget sql` prints its results
+#[derive(Clone, Copy, Debug, Default, ValueEnum)]
+pub enum SqlFormat {
+    #[default]
+    Table,
+    Csv,
+}
+
+pub fn sql(query: &str, results_path: &str, format: SqlFormat) -> color_eyre::Result<()> {
+    let connection = Connection::open_in_memory().expect("An in-memory SQLite database.");
+
+    if Path::new(results_path).exists() {
+        load(&connection, "sets", &read_results(results_path));
+    }
+    for file in dated_outputs(results_path) {
+        let mut df = read_results(&file);
+        df.with_column(Series::new("file", vec![file.as_str(); df.height()]))
+            .expect("The previous results with the file they came from.");
+        load(&connection, "previous_sets", &df);
+    }
+    if Path::new(SET_LIST_PATH).exists() {
+        load(&connection, "set_list", &read_set_list());
+    }
+    if Path::new(HISTORY_DB_PATH).exists() {
+        connection
+            .execute(
+                &format!("ATTACH DATABASE 'file:{HISTORY_DB_PATH}?mode=ro' AS history"),
+                [],
+            )
+            .expect("The history database attached.");
+    }
+
+    let mut df = query_dataframe(&connection, query)?;
+    match format {
+        SqlFormat::Table => println!("{}", &df),
+        SqlFormat::Csv => CsvWriter::new(std::io::stdout())
+            .has_header(true)
+            .finish(&mut df)
+            .expect("The query results written as csv."),
+    }
+
+    Ok(())
+}
+
+// add the rows of `df` to `table`, adding any columns it doesn't have yet
+fn load(connection: &Connection, table: &str, df: &DataFrame) {
+    let existing: Vec<String> = connection
+        .prepare(&format!("SELECT * FROM {table} LIMIT 0"))
+        .map(|statement| {
+            statement
+                .column_names()
+                .into_iter()
+                .map(String::from)
+                .collect()
+        })
+        .unwrap_or_default();
+    let definitions: Vec<String> = df
+        .get_columns()
+        .iter()
+        .filter(|series| !existing.iter().any(|name| name == series.name()))
+        .map(|series| {
+            let sql_type = match series.dtype() {
+                DataType::Float32 | DataType::Float64 => "REAL",
+                DataType::Boolean
+                | DataType::UInt32
+                | DataType::UInt64
+                | DataType::Int32
+                | DataType::Int64 => "INTEGER",
+                _ => "TEXT",
+            };
+            format!("\"{}\" {sql_type}", series.name())
+        })
+        .collect();
+    if existing.is_empty() {
+        connection
+            .execute(
+                &format!("CREATE TABLE {table} ({})", definitions.join(", ")),
+                [],
+            )
+            .unwrap_or_else(|_| panic!("The {table} table."));
+    } else {
+        for definition in definitions {
+            connection
+                .execute(&format!("ALTER TABLE {table} ADD COLUMN {definition}"), [])
+                .unwrap_or_else(|_| panic!("The {definition} column of {table}."));
+        }
+    }
+
+    let columns: Vec<String> = df
+        .get_column_names()
+        .iter()
+        .map(|name| format!("\"{name}\""))
+        .collect();
+    let placeholders = vec!["?"; columns.len()].join(", ");
+    let mut insert = connection
+        .prepare(&format!(
+            "INSERT INTO {table} ({}) VALUES ({placeholders})",
+            columns.join(", ")
+        ))
+        .unwrap_or_else(|_| panic!("A prepared insert into {table}."));
+    for row in 0..df.height() {
+        let values = df
+            .get_columns()
+            .iter()
+            .map(|series| to_sql(series.get(row)));
+        insert
+            .execute(params_from_iter(values))
+            .unwrap_or_else(|_| panic!("A row inserted into {table}."));
+    }
+}
+
+fn to_sql(value: AnyValue) -> Value {
+    match value {
+        AnyValue::Null => Value::Null,
+        AnyValue::Boolean(value) => Value::Integer(value.into()),
+        AnyValue::UInt32(value) => Value::Integer(value.into()),
+        AnyValue::Int32(value) => Value::Integer(value.into()),
+        AnyValue::Int64(value) => Value::Integer(value),
+        AnyValue::UInt64(value) => Value::Integer(value as i64),
+        AnyValue::Float32(value) => Value::Real(value.into()),
+        AnyValue::Float64(value) => Value::Real(value),
+        AnyValue::Utf8(value) => Value::Text(value.to_string()),
+        // days since the unix epoch
+        AnyValue::Date(days) => Value::Text(
+            (NaiveDate::from_ymd_opt(1970, 1, 1).expect("The unix epoch.")
+                + chrono::Duration::days(days.into()))
+            .to_string(),
+        ),
+        value => Value::Text(value.to_string()),
+    }
+}
+
+// run a query and make a column of the most general type each column holds
+fn query_dataframe(connection: &Connection, query: &str) -> color_eyre::Result<DataFrame> {
+    let mut statement = connection.prepare(query)?;
+    let names: Vec<String> = statement
+        .column_names()
+        .into_iter()
+        .map(String::from)
+        .collect();
+
+    let mut columns: Vec<Vec<Value>> = vec![vec![]; names.len()];
+    let mut rows = statement.query([])?;
+    while let Some(row) = rows.next()? {
+        for (index, column) in columns.iter_mut().enumerate() {
+            column.push(match row.get_ref(index)? {
+                ValueRef::Null => Value::Null,
+                ValueRef::Integer(value) => Value::Integer(value),
+                ValueRef::Real(value) => Value::Real(value),
+                ValueRef::Text(text) => Value::Text(String::from_utf8_lossy(text).to_string()),
+                ValueRef::Blob(blob) => Value::Text(format!("<{} bytes>", blob.len())),
+            });
+        }
+    }
+
+    let series = names
+        .iter()
+        .zip(columns)
+        .map(|(name, column)| {
+            let is_integer = column
+                .iter()
+                .all(|value| matches!(value, Value::Null | Value::Integer(_)));
+            let is_number = column
+                .iter()
+                .all(|value| matches!(value, Value::Null | Value::Integer(_) | Value::Real(_)));
+            if is_integer {
+                let column: Vec<Option<i64>> = column
+                    .into_iter()
+                    .map(|value| match value {
+                        Value::Integer(value) => Some(value),
+                        _ => None,
+                    })
+                    .collect();
+                Series::new(name, column)
+            } else if is_number {
+                let column: Vec<Option<f64>> = column
+                    .into_iter()
+                    .map(|value| match value {
+                        Value::Integer(value) => Some(value as f64),
+                        Value::Real(value) => Some(value),
+                        _ => None,
+                    })
+                    .collect();
+                Series::new(name, column)
+            } else {
+                let column: Vec<Option<String>> = column
+                    .into_iter()
+                    .map(|value| match value {
+                        Value::Null => None,
+                        Value::Integer(value) => Some(value.to_string()),
+                        Value::Real(value) => Some(value.to_string()),
+                        Value::Text(value) => Some(value),
+                        Value::Blob(_) => None,
+                    })
+                    .collect();
+                Series::new(name, column)
+            }
+        })
+        .collect();
+
+    // e.g. two columns with the same name
+    Ok(DataFrame::new(series)?)
+}
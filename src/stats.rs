@@ -0,0 +1,73 @@
+//! Summary statistics of the scan results by theme, subtheme or year
+
+use crate::results::read_results;
+
+use clap::ValueEnum;
+use polars::prelude::*;
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum StatsBy {
+    Theme,
+    /// grouped within each theme
+    Subtheme,
+    Year,
+}
+
+impl StatsBy {
+    fn columns(self) -> Vec<Expr> {
+        match self {
+            StatsBy::Theme => vec![col("theme")],
+            StatsBy::Subtheme => vec![col("theme"), col("subtheme")],
+            StatsBy::Year => vec![col("year")],
+        }
+    }
+}
+
+/// print a row of medians per group with each group's best deal
+pub fn stats(results_path: &str, by: StatsBy) -> color_eyre::Result<()> {
+    let df = read_results(results_path);
+
+    // results from before --score rank by discount, where lower is better
+    let (best_by, descending) = if df.column("score").is_ok() {
+        ("score", true)
+    } else {
+        ("percent_discount_from_value", false)
+    };
+
+    let stats = df
+        .lazy()
+        .sort(
+            best_by,
+            SortOptions {
+                descending,
+                nulls_last: true,
+            },
+        )
+        .groupby(by.columns())
+        .agg([
+            col("set_number").count().alias("sets"),
+            col("pieces").median().alias("median_pieces"),
+            col("retail_price").median().alias("median_retail_price"),
+            col("value").median().alias("median_value"),
+            (col("value") / col("retail_price"))
+                .median()
+                .alias("median_value_to_retail"),
+            (col("listed_price") / col("pieces"))
+                .median()
+                .alias("median_price_per_piece"),
+            col("percent_discount_from_value")
+                .median()
+                .alias("median_discount_from_value"),
+            // sorted best first so the first of each group is its best deal
+            col("set_number").first().alias("best_deal"),
+            col("name").first().alias("best_deal_name"),
+            col(best_by).first().alias(&format!("best_deal_{best_by}")),
+        ])
+        .sort_by_exprs(by.columns(), vec![false; by.columns().len()], true)
+        .collect()
+        .expect("The summary statistics of the results.");
+
+    println!("{}", &stats);
+
+    Ok(())
+}
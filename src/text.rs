@@ -0,0 +1,161 @@
+//! Pulling clean text out of scraped elements
+//!
+//! `inner_html()` keeps entities like `&amp;` and any nested tags so every
+//! field should be read through `element_text()` instead.
+
+use lazy_static::lazy_static;
+use markup5ever::data::NAMED_ENTITIES;
+use regex::{Captures, Regex};
+use scraper::ElementRef;
+use unicode_normalization::UnicodeNormalization;
+
+lazy_static! {
+    // entities that survive parsing because the page escaped them twice
+    static ref RE_ENTITY: Regex = Regex::new(r"&(#[0-9]+|#[xX][0-9a-fA-F]+|[A-Za-z][A-Za-z0-9]*);").expect("A Regex of an html entity.");
+}
+
+/// the decoded text of an element and everything nested in it
+pub fn element_text(element: &ElementRef) -> String {
+    normalize(&element.text().collect::<String>())
+}
+
+/// decode leftover entities, normalize to NFC and collapse whitespace
+pub fn normalize(text: &str) -> String {
+    let decoded = RE_ENTITY.replace_all(text, |captures: &Captures| {
+        decode_entity(&captures[1]).unwrap_or_else(|| captures[0].to_string())
+    });
+
+    decoded
+        .nfc()
+        // zero width characters sneak into names copied from elsewhere
+        .filter(|c| !matches!(c, '\u{200B}'..='\u{200D}' | '\u{FEFF}'))
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+// any of the html5 named entities, e.g. &trade; or &eacute;, or a numeric one
+fn decode_entity(entity: &str) -> Option<String> {
+    let code = match entity.strip_prefix("#x").or(entity.strip_prefix("#X")) {
+        Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+        None => match entity.strip_prefix('#') {
+            Some(decimal) => decimal.parse().ok()?,
+            None => {
+                let &(first, second) = NAMED_ENTITIES.get(format!("{entity};").as_str())?;
+                return [first, second]
+                    .into_iter()
+                    .filter(|&code| code != 0)
+                    .map(char::from_u32)
+                    .collect();
+            }
+        },
+    };
+    char::from_u32(code).map(String::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use scraper::{Html, Selector};
+
+    fn first_text(html: &str, selector: &str) -> String {
+        let document = Html::parse_fragment(html);
+        let selector = Selector::parse(selector).unwrap();
+        let element = document.select(&selector).next().unwrap();
+        element_text(&element)
+    }
+
+    #[test]
+    fn entities() {
+        assert_eq!(
+            first_text("<h1>Tower Bridge &amp; Friends</h1>", "h1"),
+            "Tower Bridge & Friends"
+        );
+        assert_eq!(
+            first_text("<h1>Santa&#39;s Workshop</h1>", "h1"),
+            "Santa's Workshop"
+        );
+        assert_eq!(first_text("<h1>R2-D2&#x2122;</h1>", "h1"), "R2-D2™");
+    }
+
+    #[test]
+    fn double_escaped_entities() {
+        // the parser decodes one level and normalize the other
+        assert_eq!(
+            first_text(
+                "<h1>Harry Potter&amp;trade; &amp;amp; Hogwarts&amp;#39;</h1>",
+                "h1"
+            ),
+            "Harry Potter™ & Hogwarts'"
+        );
+        assert_eq!(
+            normalize("LEGO&reg; Pok&eacute;mon&trade; Pikachu &ndash; 2024"),
+            "LEGO® Pokémon™ Pikachu – 2024"
+        );
+        assert_eq!(
+            normalize("Batman&amp;quot;s &amp;lt;Batmobile&amp;gt;"),
+            "Batman&quot;s &lt;Batmobile&gt;"
+        );
+        assert_eq!(
+            normalize("Batman&quot;s &lt;Batmobile&gt;"),
+            "Batman\"s <Batmobile>"
+        );
+        assert_eq!(normalize("Caf&#xE9; &#233;"), "Café é");
+    }
+
+    #[test]
+    fn named_and_unknown_entities() {
+        assert_eq!(normalize("Star Wars&trade;"), "Star Wars™");
+        assert_eq!(
+            normalize("Star Wars&notanentity;"),
+            "Star Wars&notanentity;"
+        );
+        assert_eq!(normalize("Bricks & Pieces; Co"), "Bricks & Pieces; Co");
+        assert_eq!(normalize("&#1114112; &#xZZ;"), "&#1114112; &#xZZ;");
+    }
+
+    #[test]
+    fn nested_tags() {
+        assert_eq!(
+            first_text(
+                "<div class=\"name\">LEGO <b>Icons</b> <b><i>Back to the</i> Future</b>&trade; Time Machine</div>",
+                "div"
+            ),
+            "LEGO Icons Back to the Future™ Time Machine"
+        );
+    }
+
+    #[test]
+    fn whitespace() {
+        assert_eq!(
+            first_text(
+                "<span>\n   Millennium&nbsp;Falcon\u{A0}\t  Microfighter \n</span>",
+                "span"
+            ),
+            "Millennium Falcon Microfighter"
+        );
+        assert_eq!(normalize("Ninjago&nbsp;City"), "Ninjago City");
+        assert_eq!(normalize("   "), "");
+    }
+
+    #[test]
+    fn zero_width_characters() {
+        assert_eq!(
+            normalize("\u{FEFF}Hogwarts\u{200B} Castle\u{200D}"),
+            "Hogwarts Castle"
+        );
+    }
+
+    #[test]
+    fn nfc() {
+        let decomposed = "Poke\u{301}mon Pikachu";
+        let composed = "Pok\u{E9}mon Pikachu";
+        assert_ne!(decomposed, composed);
+        assert_eq!(normalize(decomposed), composed);
+        assert_eq!(
+            first_text(&format!("<h1>{decomposed}</h1>"), "h1"),
+            composed
+        );
+    }
+}
@@ -0,0 +1,39 @@
+//! Sets we want to buy and the most we'd pay for them
+//!
+//! The watchlist is a csv with columns `set_number,target_price` where the
+//! target price is in the output currency.
+
+use crate::set_list::with_variant;
+
+use polars::prelude::*;
+
+pub const WATCHLIST_PATH: &str = "watchlist.csv";
+
+/// what `leget check` exits with when a watched set is at or below its target
+pub const WATCHLIST_HIT_EXIT_CODE: i32 = 3;
+
+pub fn read_watchlist(path: &str) -> DataFrame {
+    let mut schema = Schema::new();
+    schema.with_column("set_number".to_string(), DataType::Utf8);
+    schema.with_column("target_price".to_string(), DataType::Float64);
+
+    let mut df = CsvReader::from_path(path)
+        .expect("A reader connection to the watchlist.")
+        .with_dtypes(Some(&schema))
+        .has_header(true)
+        .finish()
+        .expect("A polars DataFrame from the watchlist.");
+
+    let set_numbers: Vec<String> = df
+        .column("set_number")
+        .expect("The set numbers of the watchlist.")
+        .utf8()
+        .expect("Set numbers as utf8.")
+        .into_no_null_iter()
+        .map(with_variant)
+        .collect();
+    df.with_column(Series::new("set_number", set_numbers))
+        .expect("The watchlist with full set numbers.");
+
+    df
+}